@@ -47,6 +47,51 @@ pub enum K2TreeError {
     ///
     max_x_y: [usize; 2],
   },
+  /// Produced when a user attempts to multiply the matrices of two K2Trees
+  /// where the width of the first is not the height of the second.
+  DimensionMismatch {
+    /// The width of the left-hand matrix.
+    width: usize,
+    /// The height of the right-hand matrix.
+    other_height: usize,
+  },
+  /// Produced when a user attempts an operation that needs a square matrix,
+  /// such as taking a power or treating it as a graph, on one that is not.
+  NotSquare {
+    /// The width of the matrix.
+    width: usize,
+    /// The height of the matrix.
+    height: usize,
+  },
+  /// Produced when a K2TreeBuilder is given a coordinate that comes before
+  /// the previous coordinate it was given.
+  UnorderedInput {
+    /// The coordinates that were out of order.
+    x_y: [usize; 2],
+    /// The coordinates given before them.
+    prev_x_y: [usize; 2],
+  },
+  /// Produced when a K2Tree would have to cover a matrix whose matrix_width does
+  /// not fit in a usize, to hold a coordinate or because of its k values.
+  OversizedMatrix {
+    /// The k value of each stem-layer from the root down.
+    stem_ks: Vec<usize>,
+    /// The k value of the leaves.
+    leaf_k: usize,
+  },
+  /// Produced when reading or writing a K2Tree in its binary format fails.
+  Io {
+    /// The kind of the underlying I/O error.
+    kind: std::io::ErrorKind,
+    /// The message of the underlying I/O error.
+    reason: String,
+  },
+  /// Produced when the input to `K2Tree::read_from` is not a valid K2Tree in
+  /// the binary format.
+  InvalidFormat {
+    /// What about the input is invalid.
+    reason: String,
+  },
   /// Produced when a stem could not be inserted into a K2Tree's stems.
   StemInsertionError {
     /// The index the stem-insertion was attempted at.
@@ -106,6 +151,12 @@ pub enum K2TreeError {
   SubRangesError {
     ///
     source: Box<SubRangesError>,
+  },
+  /// Propogation of a StructureError, produced when the stems and leaves of a
+  /// K2Tree do not describe a valid K2Tree.
+  StructureError {
+    /// The invariant that does not hold.
+    source: Box<StructureError>,
   }
 }
 impl std::error::Error for K2TreeError {
@@ -117,6 +168,7 @@ impl std::error::Error for K2TreeError {
       Write{source} => Some(source),
       BitMatrixError{source} => Some(source),
       SubRangesError{source} => Some(source),
+      StructureError{source} => Some(source),
       _ => None,
     }
   }
@@ -133,6 +185,15 @@ impl std::fmt::Display for K2TreeError {
         min_x_y: [min_x, min_y],
         max_x_y: [max_x, max_y]
       } => write!(f, "Attempt to access a bit at coordiantes ({}, {}) which are not in the range of the matrix represented by the K2Tree: ({}, {}) -> ({}, {})", x, y, min_x, min_y, max_x, max_y),
+      DimensionMismatch{width, other_height} => write!(f, "Attempt to multiply a matrix of width {} by a matrix of height {}", width, other_height),
+      NotSquare{width, height} => write!(f, "Attempt to use a matrix of width {} and height {} as a square matrix", width, height),
+      UnorderedInput {
+        x_y: [x, y],
+        prev_x_y: [prev_x, prev_y]
+      } => write!(f, "Attempt to build a K2Tree from coordinates ({}, {}) which come before the previous coordinates ({}, {})", x, y, prev_x, prev_y),
+      OversizedMatrix{stem_ks, leaf_k} => write!(f, "A K2Tree with stem-layers of k values {:?} and a leaf_k of {} would cover a matrix too wide for a usize.", stem_ks, leaf_k),
+      Io{reason, ..} => write!(f, "I/O error: {}", reason),
+      InvalidFormat{reason} => write!(f, "Input is not a valid K2Tree: {}", reason),
       StemInsertionError{pos, len} => write!(f, "Could not insert stem of length {} at index {}", len, pos),
       StemRemovalError{pos, len} => write!(f, "Could not remove stem of length {} at index {}", len, pos),
       LeafInsertionError{pos, len} => write!(f, "Could not insert leaf of length {} at index {}", len, pos),
@@ -143,6 +204,7 @@ impl std::fmt::Display for K2TreeError {
       Write{source} => write!(f, "Error during write: {}", source),
       BitMatrixError{source} => write!(f, "{}", source),
       SubRangesError{source} => write!(f, "{}", source),
+      StructureError{source} => write!(f, "{}", source),
     }
   }
 }
@@ -153,6 +215,14 @@ impl From<BitMatrixError> for K2TreeError {
     }
   }
 }
+impl From<std::io::Error> for K2TreeError {
+  fn from(error: std::io::Error) -> Self {
+    K2TreeError::Io {
+      kind: error.kind(),
+      reason: error.to_string(),
+    }
+  }
+}
 impl From<SubRangesError> for K2TreeError {
   fn from(error: SubRangesError) -> Self {
     K2TreeError::SubRangesError {
@@ -160,6 +230,13 @@ impl From<SubRangesError> for K2TreeError {
     }
   }
 }
+impl From<StructureError> for K2TreeError {
+  fn from(error: StructureError) -> Self {
+    K2TreeError::StructureError {
+      source: Box::new(error),
+    }
+  }
+}
 
 /// Errors produced as a result of interactions with the BitMatrix object.
 #[derive(Clone, Debug, PartialEq)]
@@ -220,4 +297,126 @@ impl std::fmt::Display for SubRangesError {
       } => write!(f, "The 2D range ({}, {}) -> ({}, {}) could not be evenly subdivided {} times horizontally or {} times vertically.", min_x, min_y, max_x, max_y, hs, vs),
     }
   }
-}
\ No newline at end of file
+}
+
+/// Errors describing which of the invariants of a K2Tree's structure does not hold.
+/// 
+/// Layers are counted from 0 at the root, and blocks are counted from 0 at the start
+/// of the stems or leaves.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StructureError {
+  /// The stem_k is below 2.
+  SmallStemKValue {
+    /// The stem_k of the K2Tree.
+    stem_k: usize,
+  },
+  /// The leaf_k is below 2.
+  SmallLeafKValue {
+    /// The leaf_k of the K2Tree.
+    leaf_k: usize,
+  },
+  /// The K2Tree has no stem-layers.
+  NoStemLayers,
+  /// The length of the stems is not a whole, non-zero number of blocks.
+  PartialStem {
+    /// The length of the stems.
+    len: usize,
+    /// The length of each block of stems.
+    stem_len: usize,
+  },
+  /// The length of the leaves is not a whole number of blocks.
+  PartialLeaf {
+    /// The length of the leaves.
+    len: usize,
+    /// The length of each block of leaves.
+    leaf_len: usize,
+  },
+  /// The number of stems in a layer is not the number of 1s in the layer above.
+  /// 
+  /// A layer of `max_slayers` refers to stems after the end of the last stem-layer.
+  StemCount {
+    /// The layer with the wrong number of stems.
+    layer: usize,
+    /// The number of stems the layer above points to.
+    expected: usize,
+    /// The number of stems left from the start of the layer.
+    found: usize,
+  },
+  /// The number of leaves is not the number of 1s in the last stem-layer.
+  LeafCount {
+    /// The number of leaves the last stem-layer points to.
+    expected: usize,
+    /// The number of leaves.
+    found: usize,
+  },
+  /// A stem other than the root of an empty K2Tree contains only 0s.
+  EmptyStem {
+    /// The layer of the stem.
+    layer: usize,
+    /// The block number of the stem.
+    stem: usize,
+  },
+  /// A leaf contains only 0s.
+  EmptyLeaf {
+    /// The block number of the leaf.
+    leaf: usize,
+  },
+  /// The width or height of the matrix is greater than the matrix_width.
+  OversizedDimensions {
+    /// The width of the matrix.
+    width: usize,
+    /// The height of the matrix.
+    height: usize,
+    /// The width of the square matrix the stems and leaves cover.
+    matrix_width: usize,
+  },
+  /// There is a 1 outside of the width and height of the matrix.
+  OneOutsideDimensions {
+    /// The coordinates of the 1.
+    x_y: [usize; 2],
+  },
+  /// The index used to navigate the K2Tree does not match its stems or leaves, which
+  /// happens when they are modified directly without calling `K2Tree::reindex` afterwards.
+  StaleIndex,
+  /// A block has more bits than fit in a usize.
+  OversizedBlock {
+    /// The k value of the block.
+    k: usize,
+  },
+  /// The k values of the stem-layers do not match the stem_k and number of stem-layers,
+  /// which must be the k value of the root and the number of k values.
+  StemKsMismatch {
+    /// The stem_k of the K2Tree.
+    stem_k: usize,
+    /// The number of stem-layers of the K2Tree.
+    max_slayers: usize,
+    /// The k value of each stem-layer, from the root down.
+    stem_ks: Vec<usize>,
+  },
+}
+impl std::error::Error for StructureError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    None
+  }
+}
+impl std::fmt::Display for StructureError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use StructureError::*;
+    match self {
+      SmallStemKValue{stem_k} => write!(f, "The K2Tree has a stem_k value of {}, which is less than the minimum of 2.", stem_k),
+      SmallLeafKValue{leaf_k} => write!(f, "The K2Tree has a leaf_k value of {}, which is less than the minimum of 2.", leaf_k),
+      NoStemLayers => write!(f, "The K2Tree has no stem-layers."),
+      PartialStem{len, stem_len} => write!(f, "The K2Tree's stems have length {}, which is not a non-zero multiple of the stem length {}.", len, stem_len),
+      PartialLeaf{len, leaf_len} => write!(f, "The K2Tree's leaves have length {}, which is not a multiple of the leaf length {}.", len, leaf_len),
+      StemCount{layer, expected, found} => write!(f, "Stem-layer {} of the K2Tree should have {} stems, but {} were found.", layer, expected, found),
+      LeafCount{expected, found} => write!(f, "The K2Tree should have {} leaves, but {} were found.", expected, found),
+      EmptyStem{layer, stem} => write!(f, "Stem {} in stem-layer {} of the K2Tree contains only 0s.", stem, layer),
+      EmptyLeaf{leaf} => write!(f, "Leaf {} of the K2Tree contains only 0s.", leaf),
+      OversizedDimensions{width, height, matrix_width} => write!(f, "The K2Tree has a width of {} and a height of {}, but it only covers a matrix of width {}.", width, height, matrix_width),
+      OneOutsideDimensions{x_y: [x, y]} => write!(f, "The K2Tree has a 1 at ({}, {}), outside of its width and height.", x, y),
+      StaleIndex => write!(f, "The K2Tree's index does not match its stems or leaves, it must be rebuilt with reindex."),
+      OversizedBlock{k} => write!(f, "A block of the K2Tree with a k value of {} has more bits than fit in a usize.", k),
+      StemKsMismatch{stem_k, max_slayers, stem_ks} => write!(f, "The K2Tree has a stem_k of {} and {} stem-layers, which do not match the k values of its stem-layers {:?}.", stem_k, max_slayers, stem_ks),
+    }
+  }
+}
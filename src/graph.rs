@@ -0,0 +1,761 @@
+use {
+  std::collections::VecDeque,
+  bitvec::prelude::{bitvec, BitVec},
+  crate::{error::K2TreeError as Error, tree::{K2Tree, Successors, one_positions_bv}},
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// An iterator over the nodes reachable from a source node, in breadth-first order.
+///
+/// The source is produced first, then every node one edge away from it, then
+/// every node two edges away and so on. Nodes with the same distance from the source
+/// are produced in the order they were first found, and each node only ever appears once.
+#[derive(Debug)]
+pub struct Bfs<'a> {
+  tree: &'a K2Tree,
+  queue: VecDeque<usize>,
+  visited: BitVec,
+}
+impl<'a> Iterator for Bfs<'a> {
+  type Item = usize;
+  fn next(&mut self) -> Option<Self::Item> {
+    let node = self.queue.pop_front()?;
+    /* `bfs` checked that the matrix is square, so every node found has a row */
+    debug_assert!(node < self.tree.height());
+    for neighbour in Successors::new(self.tree, node) {
+      if !self.visited[neighbour] {
+        self.visited.set(neighbour, true);
+        self.queue.push_back(neighbour);
+      }
+    }
+    Some(node)
+  }
+}
+
+/// An iterator over the nodes reachable from a source node, in depth-first order.
+///
+/// The source is produced first, then each of its neighbours in ascending order,
+/// each followed by everything reachable from it that has not already been produced.
+/// Each node only ever appears once.
+#[derive(Debug)]
+pub struct Dfs<'a> {
+  tree: &'a K2Tree,
+  stack: Vec<usize>,
+  visited: BitVec,
+}
+impl<'a> Iterator for Dfs<'a> {
+  type Item = usize;
+  fn next(&mut self) -> Option<Self::Item> {
+    while let Some(node) = self.stack.pop() {
+      if self.visited[node] { continue }
+      self.visited.set(node, true);
+      /* Push in reverse so the neighbours are popped in order. `dfs` checked
+      that the matrix is square, so every node found has a row */
+      debug_assert!(node < self.tree.height());
+      let neighbours: Vec<usize> = Successors::new(self.tree, node).collect();
+      for &neighbour in neighbours.iter().rev() {
+        if !self.visited[neighbour] { self.stack.push(neighbour); }
+      }
+      return Some(node)
+    }
+    None
+  }
+}
+
+/// Returns an iterator over the nodes reachable from `source` in breadth-first order,
+/// treating `tree` as the adjacency matrix of a directed graph.
+///
+/// There is an edge from node `y` to node `x` wherever there is a 1 at (x, y),
+/// so the neighbours of a node are the 1s in its row.
+///
+/// Returns a NotSquare error if the matrix is not square, or an OutOfBounds
+/// error if `source` is not a node of the graph.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Edges 0 -> 1, 0 -> 2, 1 -> 3 and 2 -> 3 */
+///   let tree = K2Tree::from_coords(vec![(1, 0), (2, 0), (3, 1), (3, 2)], 2, 2)?;
+///   let order: Vec<usize> = graph::bfs(&tree, 0)?.collect();
+///   assert_eq!(vec![0, 1, 2, 3], order);
+///   Ok(())
+/// }
+/// ```
+pub fn bfs(tree: &K2Tree, source: usize) -> Result<Bfs<'_>> {
+  let visited = visited_from(tree, source)?;
+  let mut queue = VecDeque::new();
+  queue.push_back(source);
+  Ok(Bfs {
+    tree,
+    queue,
+    visited,
+  })
+}
+/// Returns an iterator over the nodes reachable from `source` in depth-first order,
+/// treating `tree` as the adjacency matrix of a directed graph.
+///
+/// See `bfs` for how the graph is read from the K2Tree.
+///
+/// Returns a NotSquare error if the matrix is not square, or an OutOfBounds
+/// error if `source` is not a node of the graph.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Edges 0 -> 1, 0 -> 2, 1 -> 3 and 2 -> 3 */
+///   let tree = K2Tree::from_coords(vec![(1, 0), (2, 0), (3, 1), (3, 2)], 2, 2)?;
+///   let order: Vec<usize> = graph::dfs(&tree, 0)?.collect();
+///   assert_eq!(vec![0, 1, 3, 2], order);
+///   Ok(())
+/// }
+/// ```
+pub fn dfs(tree: &K2Tree, source: usize) -> Result<Dfs<'_>> {
+  check_node(tree, source)?;
+  let visited = bitvec![0; tree.width()];
+  Ok(Dfs {
+    tree,
+    stack: vec![source],
+    visited,
+  })
+}
+/// Returns every node reachable from `source`, including `source` itself,
+/// in ascending order.
+///
+/// Returns a NotSquare error if the matrix is not square, or an OutOfBounds
+/// error if `source` is not a node of the graph.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Edges 0 -> 1, 1 -> 3 and 5 -> 0 */
+///   let tree = K2Tree::from_coords(vec![(1, 0), (3, 1), (0, 5)], 2, 2)?;
+///   assert_eq!(vec![0, 1, 3], graph::reachable(&tree, 0)?);
+///   assert_eq!(vec![0, 1, 3, 5], graph::reachable(&tree, 5)?);
+///   Ok(())
+/// }
+/// ```
+pub fn reachable(tree: &K2Tree, source: usize) -> Result<Vec<usize>> {
+  let mut nodes: Vec<usize> = bfs(tree, source)?.collect();
+  nodes.sort_unstable();
+  Ok(nodes)
+}
+/// Returns every node that can be reached from `source` by following at most
+/// `hops` edges, including `source` itself, in ascending order.
+///
+/// Each step expands the whole frontier of newly found nodes at once, so only
+/// the parts of the K2Tree overlapping the frontier's rows are visited.
+///
+/// Returns a NotSquare error if the matrix is not square, or an OutOfBounds
+/// error if `source` is not a node of the graph.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Edges 0 -> 1, 1 -> 2 and 2 -> 3 */
+///   let tree = K2Tree::from_coords(vec![(1, 0), (2, 1), (3, 2)], 2, 2)?;
+///   assert_eq!(vec![0], graph::neighbourhood(&tree, 0, 0)?);
+///   assert_eq!(vec![0, 1, 2], graph::neighbourhood(&tree, 0, 2)?);
+///   assert_eq!(vec![2, 3], graph::neighbourhood(&tree, 2, 5)?);
+///   Ok(())
+/// }
+/// ```
+pub fn neighbourhood(tree: &K2Tree, source: usize, hops: usize) -> Result<Vec<usize>> {
+  let mut visited = visited_from(tree, source)?;
+  let mut frontier = vec![source];
+  for _ in 0..hops {
+    frontier = tree.mul_vector(&frontier)
+      .into_iter()
+      .filter(|&node| !visited[node])
+      .collect();
+    if frontier.is_empty() { break }
+    for &node in frontier.iter() {
+      visited.set(node, true);
+    }
+  }
+  Ok(one_positions_bv(&visited))
+}
+/// Returns the weakly connected component of every node of the graph, ignoring
+/// the direction of its edges.
+///
+/// The result has an entry for every node up to the `width`, and two nodes have
+/// the same entry if and only if they are in the same component. Components are
+/// numbered from 0 in the order of their lowest node.
+///
+/// Both the rows and the columns of the K2Tree are used to find each node's neighbours,
+/// so the adjacency matrix is never decompressed.
+///
+/// Returns a NotSquare error if the matrix is not square.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Edges 0 -> 1, 2 -> 1 and 3 -> 4 */
+///   let tree = K2Tree::from_coords(vec![(1, 0), (1, 2), (4, 3)], 2, 2)?;
+///   let components = graph::weakly_connected_components(&tree)?;
+///   assert_eq!(vec![0, 0, 0, 1, 1, 2, 3, 4], components);
+///   Ok(())
+/// }
+/// ```
+pub fn weakly_connected_components(tree: &K2Tree) -> Result<Vec<usize>> {
+  tree.check_square()?;
+  let width = tree.width();
+  let mut components = vec![usize::MAX; width];
+  let mut num_components = 0;
+  let mut queue = VecDeque::new();
+  for source in 0..width {
+    if components[source] != usize::MAX { continue }
+    components[source] = num_components;
+    queue.push_back(source);
+    while let Some(node) = queue.pop_front() {
+      let successors = tree.iter_successors(node)?;
+      let predecessors = tree.iter_predecessors(node)?;
+      for neighbour in successors.chain(predecessors) {
+        if components[neighbour] == usize::MAX {
+          components[neighbour] = num_components;
+          queue.push_back(neighbour);
+        }
+      }
+    }
+    num_components += 1;
+  }
+  Ok(components)
+}
+/// Returns the strongly connected component of every node of the graph, where two nodes
+/// are in the same component if each can be reached from the other.
+///
+/// The result is laid out and numbered the same way as that of `weakly_connected_components`.
+///
+/// Uses Kosaraju's algorithm, following rows of the K2Tree for the first pass and
+/// columns for the second, so the adjacency matrix is never decompressed or transposed.
+///
+/// Returns a NotSquare error if the matrix is not square.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Edges 0 -> 1, 1 -> 0, 1 -> 2, 2 -> 3 and 3 -> 2 */
+///   let tree = K2Tree::from_coords(vec![(1, 0), (0, 1), (2, 1), (3, 2), (2, 3)], 2, 2)?;
+///   let components = graph::strongly_connected_components(&tree)?;
+///   assert_eq!(vec![0, 0, 1, 1, 2, 3, 4, 5], components);
+///   Ok(())
+/// }
+/// ```
+pub fn strongly_connected_components(tree: &K2Tree) -> Result<Vec<usize>> {
+  tree.check_square()?;
+  let width = tree.width();
+  /* First pass: order the nodes by when a depth-first search finishes with them */
+  let mut visited = bitvec![0; width];
+  let mut finished = Vec::with_capacity(width);
+  let mut stack: Vec<(usize, Vec<usize>)> = Vec::new();
+  for source in 0..width {
+    if visited[source] { continue }
+    visited.set(source, true);
+    stack.push((source, tree.successors(source)?));
+    while let Some((node, neighbours)) = stack.last_mut() {
+      match neighbours.pop() {
+        Some(neighbour) => if !visited[neighbour] {
+          visited.set(neighbour, true);
+          let neighbours = tree.successors(neighbour)?;
+          stack.push((neighbour, neighbours));
+        },
+        None => {
+          finished.push(*node);
+          stack.pop();
+        },
+      }
+    }
+  }
+  /* Second pass: every node reachable backwards from the last node to finish,
+  that isn't already in a component, is in its component */
+  let mut components = vec![usize::MAX; width];
+  let mut num_components = 0;
+  let mut queue = VecDeque::new();
+  for &source in finished.iter().rev() {
+    if components[source] != usize::MAX { continue }
+    components[source] = num_components;
+    queue.push_back(source);
+    while let Some(node) = queue.pop_front() {
+      for neighbour in tree.iter_predecessors(node)? {
+        if components[neighbour] == usize::MAX {
+          components[neighbour] = num_components;
+          queue.push_back(neighbour);
+        }
+      }
+    }
+    num_components += 1;
+  }
+  Ok(renumber(components, num_components))
+}
+/// Returns the out-degree and in-degree of every node of the graph, in that order.
+///
+/// Both are found in a single pass over the 1s in the K2Tree's leaves, so this is
+/// much faster than asking for the degree of every node one at a time.
+///
+/// Returns a NotSquare error if the matrix is not square.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Edges 0 -> 1, 0 -> 2 and 2 -> 1 */
+///   let tree = K2Tree::from_coords(vec![(1, 0), (2, 0), (1, 2)], 2, 2)?;
+///   let (out_degrees, in_degrees) = graph::degrees(&tree)?;
+///   assert_eq!(vec![2, 0, 1, 0, 0, 0, 0, 0], out_degrees);
+///   assert_eq!(vec![0, 2, 1, 0, 0, 0, 0, 0], in_degrees);
+///   Ok(())
+/// }
+/// ```
+pub fn degrees(tree: &K2Tree) -> Result<(Vec<usize>, Vec<usize>)> {
+  tree.check_square()?;
+  let width = tree.width();
+  let mut out_degrees = vec![0; width];
+  let mut in_degrees = vec![0; width];
+  for leaf_bit in tree.leaves().filter(|leaf_bit| leaf_bit.value) {
+    out_degrees[leaf_bit.y] += 1;
+    in_degrees[leaf_bit.x] += 1;
+  }
+  Ok((out_degrees, in_degrees))
+}
+/// Returns the number of edges leaving `node`, which is the number of 1s in its row.
+///
+/// Returns a NotSquare error if the matrix is not square, or an OutOfBounds
+/// error if `node` is not a node of the graph.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   let tree = K2Tree::from_coords(vec![(1, 0), (2, 0), (1, 2)], 2, 2)?;
+///   assert_eq!(2, graph::out_degree(&tree, 0)?);
+///   assert_eq!(0, graph::out_degree(&tree, 1)?);
+///   Ok(())
+/// }
+/// ```
+pub fn out_degree(tree: &K2Tree, node: usize) -> Result<usize> {
+  check_node(tree, node)?;
+  tree.count_in_range(0..=tree.width()-1, node..=node)
+}
+/// Returns the number of edges entering `node`, which is the number of 1s in its column.
+///
+/// Returns a NotSquare error if the matrix is not square, or an OutOfBounds
+/// error if `node` is not a node of the graph.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   let tree = K2Tree::from_coords(vec![(1, 0), (2, 0), (1, 2)], 2, 2)?;
+///   assert_eq!(2, graph::in_degree(&tree, 1)?);
+///   assert_eq!(0, graph::in_degree(&tree, 0)?);
+///   Ok(())
+/// }
+/// ```
+pub fn in_degree(tree: &K2Tree, node: usize) -> Result<usize> {
+  check_node(tree, node)?;
+  tree.count_in_range(node..=node, 0..=tree.width()-1)
+}
+/// Returns the PageRank of every node of the graph, which sum to 1.
+///
+/// Each iteration spreads every node's rank evenly along its edges, with a
+/// `1 - damping` chance of jumping to any node instead. Nodes with no edges spread
+/// their rank evenly over every node. Iterating stops once the ranks change by less
+/// than `tolerance` in total, or after `max_iterations` iterations.
+///
+/// Every node up to the `width` is ranked, and the edges are read straight
+/// from the K2Tree on each iteration.
+///
+/// Returns a NotSquare error if the matrix is not square.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Every node links to node 0 */
+///   let tree = K2Tree::from_coords((1..8).map(|node| (0, node)), 2, 2)?;
+///   let ranks = graph::pagerank(&tree, 0.85, 1e-9, 100)?;
+///   assert!((ranks.iter().sum::<f64>() - 1.0).abs() < 1e-6);
+///   assert!(ranks[1..].iter().all(|&rank| rank < ranks[0]));
+///   Ok(())
+/// }
+/// ```
+pub fn pagerank(tree: &K2Tree, damping: f64, tolerance: f64, max_iterations: usize) -> Result<Vec<f64>> {
+  tree.check_square()?;
+  let width = tree.width();
+  if width == 0 { return Ok(Vec::new()) }
+  let num_nodes = width as f64;
+  let (out_degrees, _) = degrees(tree)?;
+  let mut ranks = vec![1.0 / num_nodes; width];
+  for _ in 0..max_iterations {
+    /* The rank of nodes with no edges goes to every node */
+    let dangling: f64 = (0..width)
+      .filter(|&node| out_degrees[node] == 0)
+      .map(|node| ranks[node])
+      .sum();
+    let base = (1.0 - damping) / num_nodes + damping * dangling / num_nodes;
+    let mut new_ranks = vec![base; width];
+    for (x, y) in tree.iter_ones_in_range(0..=width-1, 0..=width-1)? {
+      new_ranks[x] += damping * ranks[y] / out_degrees[y] as f64;
+    }
+    let change: f64 = ranks.iter()
+      .zip(new_ranks.iter())
+      .map(|(rank, new_rank)| (rank - new_rank).abs())
+      .sum();
+    ranks = new_ranks;
+    if change < tolerance { break }
+  }
+  Ok(ranks)
+}
+/// Returns the number of triangles in the graph, treating `tree` as the adjacency
+/// matrix of an undirected graph.
+///
+/// The matrix of an undirected graph is symmetric, with a 1 at (x, y) wherever there
+/// is a 1 at (y, x). The result is meaningless if the K2Tree is not symmetric.
+/// Self-loops are ignored.
+///
+/// Triangles are found by intersecting the sorted neighbour lists of the ends of
+/// each edge, so the adjacency matrix is never decompressed.
+///
+/// Returns a NotSquare error if the matrix is not square.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Edges 0 - 1, 1 - 2, 2 - 0 and 2 - 3 */
+///   let edges = vec![(0, 1), (1, 2), (2, 0), (2, 3)];
+///   let both_ways = edges.iter().flat_map(|&(a, b)| vec![(a, b), (b, a)]);
+///   let tree = K2Tree::from_coords(both_ways, 2, 2)?;
+///   assert_eq!(1, graph::triangles(&tree)?);
+///   Ok(())
+/// }
+/// ```
+pub fn triangles(tree: &K2Tree) -> Result<usize> {
+  tree.check_square()?;
+  let mut count = 0;
+  for node in 0..tree.width() {
+    /* Only count each triangle from its lowest node, through its middle node */
+    let neighbours = undirected_neighbours(tree, node)?;
+    for &neighbour in neighbours.iter().filter(|&&neighbour| neighbour > node) {
+      let neighbours_of_neighbour = undirected_neighbours(tree, neighbour)?;
+      count += sorted_intersection_len(
+        &neighbours[partition_above(&neighbours, neighbour)..],
+        &neighbours_of_neighbour[partition_above(&neighbours_of_neighbour, neighbour)..],
+      );
+    }
+  }
+  Ok(count)
+}
+/// Returns the local clustering coefficient of every node of the graph, treating
+/// `tree` as the adjacency matrix of an undirected graph.
+///
+/// A node's clustering coefficient is the fraction of the pairs of its neighbours
+/// that are also neighbours of each other. Nodes with fewer than two neighbours
+/// have a clustering coefficient of 0.
+///
+/// See `triangles` for the requirements on the K2Tree.
+///
+/// Returns a NotSquare error if the matrix is not square.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, graph};
+///   /* Edges 0 - 1, 1 - 2, 2 - 0 and 2 - 3 */
+///   let edges = vec![(0, 1), (1, 2), (2, 0), (2, 3)];
+///   let both_ways = edges.iter().flat_map(|&(a, b)| vec![(a, b), (b, a)]);
+///   let tree = K2Tree::from_coords(both_ways, 2, 2)?;
+///   let coefficients = graph::clustering_coefficients(&tree)?;
+///   assert_eq!(1.0, coefficients[0]);
+///   assert_eq!(1.0 / 3.0, coefficients[2]);
+///   assert_eq!(0.0, coefficients[3]);
+///   Ok(())
+/// }
+/// ```
+pub fn clustering_coefficients(tree: &K2Tree) -> Result<Vec<f64>> {
+  tree.check_square()?;
+  (0..tree.width())
+    .map(|node| {
+      let neighbours = undirected_neighbours(tree, node)?;
+      let degree = neighbours.len();
+      if degree < 2 { return Ok(0.0) }
+      /* Every link between two neighbours is found from both ends, so `links` is
+      already twice their number, as the coefficient needs */
+      let mut links = 0;
+      for &neighbour in neighbours.iter() {
+        links += sorted_intersection_len(&neighbours, &undirected_neighbours(tree, neighbour)?);
+      }
+      Ok(links as f64 / (degree * (degree - 1)) as f64)
+    })
+    .collect()
+}
+
+/* Private */
+/* The neighbours of a node in ascending order, without the node itself */
+fn undirected_neighbours(tree: &K2Tree, node: usize) -> Result<Vec<usize>> {
+  let mut neighbours = tree.successors(node)?;
+  neighbours.retain(|&neighbour| neighbour != node);
+  Ok(neighbours)
+}
+/* The index of the first node in `nodes` above `node` */
+fn partition_above(nodes: &[usize], node: usize) -> usize {
+  nodes.partition_point(|&other| other <= node)
+}
+/* The number of nodes in both of the ascending lists */
+fn sorted_intersection_len(a: &[usize], b: &[usize]) -> usize {
+  let [mut i, mut j, mut len] = [0, 0, 0];
+  while i < a.len() && j < b.len() {
+    match a[i].cmp(&b[j]) {
+      std::cmp::Ordering::Less => i += 1,
+      std::cmp::Ordering::Greater => j += 1,
+      std::cmp::Ordering::Equal => {
+        len += 1;
+        i += 1;
+        j += 1;
+      },
+    }
+  }
+  len
+}
+/* Renumbers components in the order of their lowest node */
+fn renumber(components: Vec<usize>, num_components: usize) -> Vec<usize> {
+  let mut new_ids = vec![usize::MAX; num_components];
+  let mut next_id = 0;
+  components.into_iter()
+    .map(|component| {
+      if new_ids[component] == usize::MAX {
+        new_ids[component] = next_id;
+        next_id += 1;
+      }
+      new_ids[component]
+    })
+    .collect()
+}
+fn check_node(tree: &K2Tree, node: usize) -> Result<()> {
+  tree.check_square()?;
+  let width = tree.width();
+  if node >= width {
+    return Err(Error::Read {
+      source: Box::new(Error::OutOfBounds {
+        x_y: [node, node],
+        min_x_y: [0, 0],
+        max_x_y: [width-1; 2]
+      })
+    })
+  }
+  Ok(())
+}
+/* A visited set for every node of the graph, with only `source` visited */
+fn visited_from(tree: &K2Tree, source: usize) -> Result<BitVec> {
+  check_node(tree, source)?;
+  let mut visited = bitvec![0; tree.width()];
+  visited.set(source, true);
+  Ok(visited)
+}
+
+#[cfg(test)]
+mod api {
+  use super::*;
+  /* 0 -> 1 -> 2 -> 0 is a cycle, 2 -> 3 -> 4, 5 -> 4 and 6 is on its own */
+  fn test_graph() -> K2Tree {
+    let edges = vec![(0, 1), (1, 2), (2, 0), (2, 3), (3, 4), (5, 4)];
+    K2Tree::from_coords(edges.into_iter().map(|(from, to)| (to, from)), 2, 2).unwrap()
+  }
+  #[test]
+  fn bfs_and_dfs() -> Result<()> {
+    let tree = test_graph();
+    assert_eq!(vec![0, 1, 2, 3, 4], bfs(&tree, 0)?.collect::<Vec<_>>());
+    assert_eq!(vec![2, 0, 3, 1, 4], bfs(&tree, 2)?.collect::<Vec<_>>());
+    assert_eq!(vec![2, 0, 1, 3, 4], dfs(&tree, 2)?.collect::<Vec<_>>());
+    assert_eq!(vec![5, 4], dfs(&tree, 5)?.collect::<Vec<_>>());
+    assert_eq!(vec![6], bfs(&tree, 6)?.collect::<Vec<_>>());
+    assert!(bfs(&tree, 8).is_err());
+    assert!(dfs(&tree, 8).is_err());
+    Ok(())
+  }
+  #[test]
+  fn dfs_visits_deepest_first() -> Result<()> {
+    /* 0 -> 1, 0 -> 3, 1 -> 2, 2 -> 3 */
+    let edges = vec![(0, 1), (0, 3), (1, 2), (2, 3)];
+    let tree = K2Tree::from_coords(edges.into_iter().map(|(from, to)| (to, from)), 3, 2)?;
+    assert_eq!(vec![0, 1, 2, 3], dfs(&tree, 0)?.collect::<Vec<_>>());
+    assert_eq!(vec![0, 1, 3, 2], bfs(&tree, 0)?.collect::<Vec<_>>());
+    Ok(())
+  }
+  #[test]
+  fn reachable_and_neighbourhood() -> Result<()> {
+    let tree = test_graph();
+    assert_eq!(vec![0, 1, 2, 3, 4], reachable(&tree, 1)?);
+    assert_eq!(vec![3, 4], reachable(&tree, 3)?);
+    assert_eq!(vec![6], reachable(&tree, 6)?);
+    assert_eq!(vec![1], neighbourhood(&tree, 1, 0)?);
+    assert_eq!(vec![1, 2], neighbourhood(&tree, 1, 1)?);
+    assert_eq!(vec![0, 1, 2, 3], neighbourhood(&tree, 1, 2)?);
+    assert_eq!(reachable(&tree, 1)?, neighbourhood(&tree, 1, 100)?);
+    assert!(neighbourhood(&tree, 8, 1).is_err());
+    Ok(())
+  }
+  #[test]
+  fn weakly_connected() -> Result<()> {
+    let tree = test_graph();
+    assert_eq!(vec![0, 0, 0, 0, 0, 0, 1, 2], weakly_connected_components(&tree)?);
+    let empty = K2Tree::with_k(3, 2)?;
+    assert_eq!((0..18).collect::<Vec<_>>(), weakly_connected_components(&empty)?);
+    /* Only a square matrix is the adjacency matrix of a graph */
+    let wide = K2Tree::with_dimensions(2, 2, 8, 5)?;
+    assert!(weakly_connected_components(&wide).is_err());
+    Ok(())
+  }
+  #[test]
+  fn strongly_connected() -> Result<()> {
+    let tree = test_graph();
+    assert_eq!(vec![0, 0, 0, 1, 2, 3, 4, 5], strongly_connected_components(&tree)?);
+    /* Two cycles joined one way, plus a self-loop */
+    let edges = vec![(0, 5), (5, 0), (5, 9), (9, 12), (12, 9), (12, 3), (3, 12), (7, 7)];
+    let tree = K2Tree::from_coords(edges.into_iter().map(|(from, to)| (to, from)), 2, 2)?;
+    let components = strongly_connected_components(&tree)?;
+    assert_eq!(16, components.len());
+    assert_eq!(components[0], components[5]);
+    assert_eq!(components[3], components[9]);
+    assert_eq!(components[3], components[12]);
+    assert_ne!(components[0], components[3]);
+    let num_components = components.iter().max().unwrap() + 1;
+    assert_eq!(13, num_components);
+    assert_eq!(vec![0, 1, 2, 3, 4, 0], components[..6].to_vec());
+    let tall = K2Tree::with_dimensions(2, 2, 5, 8)?;
+    assert!(strongly_connected_components(&tall).is_err());
+    Ok(())
+  }
+  #[test]
+  fn degrees_match_rows_and_columns() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let coords: Vec<(usize, usize)> = (0..200)
+      .map(|_| (rng.gen_range(0, 50), rng.gen_range(0, 50)))
+      .collect();
+    let random = K2Tree::from_coords(coords, 3, 2)?;
+    for tree in [test_graph(), random].iter() {
+      let (out_degrees, in_degrees) = degrees(tree)?;
+      for node in 0..tree.matrix_width() {
+        assert_eq!(tree.successors(node)?.len(), out_degrees[node]);
+        assert_eq!(tree.predecessors(node)?.len(), in_degrees[node]);
+        assert_eq!(out_degrees[node], out_degree(tree, node)?);
+        assert_eq!(in_degrees[node], in_degree(tree, node)?);
+      }
+      assert!(out_degree(tree, tree.matrix_width()).is_err());
+      assert!(in_degree(tree, tree.matrix_width()).is_err());
+    }
+    /* Only a square matrix is the adjacency matrix of a graph */
+    let wide = K2Tree::with_dimensions(2, 2, 8, 5)?;
+    assert!(degrees(&wide).is_err());
+    assert!(out_degree(&wide, 0).is_err());
+    assert!(in_degree(&wide, 0).is_err());
+    Ok(())
+  }
+  #[test]
+  fn pagerank_cycle() -> Result<()> {
+    /* Every node in a cycle has the same rank */
+    let tree = K2Tree::from_coords((0..8).map(|node| ((node + 1) % 8, node)), 2, 2)?;
+    for rank in pagerank(&tree, 0.85, 1e-12, 100)? {
+      assert!((rank - 0.125).abs() < 1e-9);
+    }
+    let tall = K2Tree::with_dimensions(2, 2, 5, 8)?;
+    assert!(pagerank(&tall, 0.85, 1e-12, 100).is_err());
+    Ok(())
+  }
+  #[test]
+  fn pagerank_known_values() -> Result<()> {
+    /* 0 -> 1, 0 -> 2, 1 -> 2, 2 -> 0 with 4..8 dangling, compared
+    against a dense power iteration */
+    let edges = [(0, 1), (0, 2), (1, 2), (2, 0)];
+    let tree = K2Tree::from_coords(edges.iter().map(|&(from, to)| (to, from)), 2, 2)?;
+    let damping = 0.85;
+    let n = 8;
+    let mut expected = vec![1.0 / n as f64; n];
+    for _ in 0..200 {
+      let mut next = vec![0.0; n];
+      for (from, &from_rank) in expected.iter().enumerate() {
+        let targets: Vec<usize> = edges.iter()
+          .filter(|&&(f, _)| f == from)
+          .map(|&(_, to)| to)
+          .collect();
+        if targets.is_empty() {
+          for rank in next.iter_mut() { *rank += damping * from_rank / n as f64; }
+        }
+        for &to in targets.iter() {
+          next[to] += damping * from_rank / targets.len() as f64;
+        }
+      }
+      for rank in next.iter_mut() { *rank += (1.0 - damping) / n as f64; }
+      expected = next;
+    }
+    let ranks = pagerank(&tree, damping, 1e-12, 200)?;
+    for (rank, expected) in ranks.iter().zip(expected.iter()) {
+      assert!((rank - expected).abs() < 1e-9);
+    }
+    Ok(())
+  }
+  /* A random undirected graph, as a symmetric K2Tree and a dense matrix */
+  fn random_undirected(nodes: usize, edges: usize) -> Result<(K2Tree, Vec<Vec<bool>>)> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut dense = vec![vec![false; nodes]; nodes];
+    let mut coords = Vec::new();
+    for _ in 0..edges {
+      let [a, b] = [rng.gen_range(0, nodes), rng.gen_range(0, nodes)];
+      dense[a][b] = true;
+      dense[b][a] = true;
+      coords.push((a, b));
+      coords.push((b, a));
+    }
+    Ok((K2Tree::from_coords(coords, 2, 2)?, dense))
+  }
+  #[test]
+  fn triangles_and_clustering() -> Result<()> {
+    let (tree, dense) = random_undirected(30, 120)?;
+    let nodes = dense.len();
+    let mut expected_triangles = 0;
+    for a in 0..nodes {
+      for b in a+1..nodes {
+        for c in b+1..nodes {
+          if dense[a][b] && dense[b][c] && dense[a][c] { expected_triangles += 1; }
+        }
+      }
+    }
+    assert_eq!(expected_triangles, triangles(&tree)?);
+    let coefficients = clustering_coefficients(&tree)?;
+    assert_eq!(tree.matrix_width(), coefficients.len());
+    for node in 0..nodes {
+      let neighbours: Vec<usize> = (0..nodes)
+        .filter(|&other| other != node && dense[node][other])
+        .collect();
+      let mut links = 0;
+      for (i, &a) in neighbours.iter().enumerate() {
+        for &b in neighbours[i+1..].iter() {
+          if dense[a][b] { links += 1; }
+        }
+      }
+      let degree = neighbours.len();
+      let expected = if degree < 2 { 0.0 }
+        else { (2 * links) as f64 / (degree * (degree - 1)) as f64 };
+      assert!((expected - coefficients[node]).abs() < 1e-12);
+    }
+    Ok(())
+  }
+  #[test]
+  fn complete_graph() -> Result<()> {
+    let coords = (0..6).flat_map(|a| (0..6).map(move |b| (a, b)));
+    let tree = K2Tree::from_coords(coords, 2, 2)?;
+    assert_eq!(20, triangles(&tree)?);
+    let coefficients = clustering_coefficients(&tree)?;
+    assert!(coefficients[..6].iter().all(|&coefficient| coefficient == 1.0));
+    assert!(coefficients[6..].iter().all(|&coefficient| coefficient == 0.0));
+    Ok(())
+  }
+  #[test]
+  fn not_square() -> Result<()> {
+    for [width, height] in [[8, 5], [5, 8]].iter() {
+      let mut tree = K2Tree::with_dimensions(2, 2, *width, *height)?;
+      tree.set(1, 0, true)?;
+      tree.set(4, 4, true)?;
+      let mismatch = Err(Error::Read {
+        source: Box::new(Error::NotSquare { width: *width, height: *height })
+      });
+      assert_eq!(mismatch, bfs(&tree, 0).map(|_| ()));
+      assert_eq!(mismatch, dfs(&tree, 0).map(|_| ()));
+      assert_eq!(mismatch, reachable(&tree, 0).map(|_| ()));
+      assert_eq!(mismatch, neighbourhood(&tree, 0, 2).map(|_| ()));
+      assert_eq!(mismatch, weakly_connected_components(&tree).map(|_| ()));
+      assert_eq!(mismatch, strongly_connected_components(&tree).map(|_| ()));
+      assert_eq!(mismatch, degrees(&tree).map(|_| ()));
+      assert_eq!(mismatch, out_degree(&tree, 0).map(|_| ()));
+      assert_eq!(mismatch, in_degree(&tree, 0).map(|_| ()));
+      assert_eq!(mismatch, pagerank(&tree, 0.85, 1e-9, 10).map(|_| ()));
+      assert_eq!(mismatch, triangles(&tree).map(|_| ()));
+      assert_eq!(mismatch, clustering_coefficients(&tree).map(|_| ()));
+    }
+    Ok(())
+  }
+}
@@ -130,4 +130,10 @@ pub mod tree;
 pub mod error;
 
 /// `BitMatrix` struct.
-pub mod matrix;
\ No newline at end of file
+pub mod matrix;
+
+/// Graph algorithms that treat a `K2Tree` as an adjacency matrix.
+///
+/// The matrix must be square, with a row and a column for every node. Every
+/// function returns a NotSquare error if it is not.
+pub mod graph;
\ No newline at end of file
@@ -0,0 +1,404 @@
+use {
+  std::io::{Read, Write},
+  bitvec::prelude::BitVec,
+  crate::{
+    error::{K2TreeError as Error, StructureError},
+    tree::{K2Tree, validate::{block_len, check_structure, checked_matrix_width}},
+  },
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The bytes every K2Tree written by `K2Tree::write_to` begins with.
+pub(crate) const MAGIC: [u8; 4] = *b"K2TR";
+/// The version of the binary format written by `K2Tree::write_to`.
+pub(crate) const VERSION: u32 = 1;
+/// The length in bytes of the fixed part of the header, which is followed by the
+/// k value of each stem-layer.
+pub(crate) const HEADER_LEN: usize = 64;
+/// The most stem-layers a header can have, more than any K2Tree whose matrix_width
+/// fits in a u64.
+const MAX_SLAYERS: usize = 64;
+
+impl K2Tree {
+  /// Writes the K2Tree to `writer` in a stable binary format, which is the same
+  /// on every platform and can be read back with `read_from`.
+  ///
+  /// All integers are little-endian. The format is a 64 byte header:
+  ///
+  /// | Bytes  | Contents                          |
+  /// |--------|-----------------------------------|
+  /// | 0..4   | The magic bytes `K2TR`            |
+  /// | 4..8   | The format version as a u32, 1    |
+  /// | 8..16  | `stem_k` as a u64                 |
+  /// | 16..24 | `leaf_k` as a u64                 |
+  /// | 24..32 | `max_slayers` as a u64            |
+  /// | 32..40 | The number of bits in the stems   |
+  /// | 40..48 | The number of bits in the leaves  |
+  /// | 48..56 | The `width` of the matrix         |
+  /// | 56..64 | The `height` of the matrix        |
+  ///
+  /// Followed by the k value of each stem-layer from the root down as a u64, which are
+  /// all `stem_k` unless the K2Tree has a k value for each stem-layer. Then the stems
+  /// and the leaves, each packed into u64 words with the first bit
+  /// in the lowest bit of the first word. Any unused bits in the last word of each are 0.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let tree = K2Tree::from_coords(vec![(0, 5), (12, 3)], 2, 2)?;
+  ///   let mut bytes = Vec::new();
+  ///   tree.write_to(&mut bytes)?;
+  ///   assert_eq!(b"K2TR", &bytes[0..4]);
+  ///   assert_eq!(tree, K2Tree::read_from(&bytes[..])?);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn write_to(&self, mut writer: impl Write) -> Result<()> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    for &value in [
+      self.stem_k,
+      self.leaf_k,
+      self.max_slayers,
+      self.stems.len(),
+      self.leaves.len(),
+      self.width(),
+      self.height(),
+    ].iter() {
+      header.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+    for stem_k in self.stem_ks() {
+      header.extend_from_slice(&(stem_k as u64).to_le_bytes());
+    }
+    writer.write_all(&header)?;
+    write_bits(&mut writer, &self.stems)?;
+    write_bits(&mut writer, &self.leaves)?;
+    writer.flush()?;
+    Ok(())
+  }
+  /// Reads a K2Tree written by `write_to` from `reader`.
+  ///
+  /// Returns an InvalidFormat error if the input does not begin with the magic bytes, is
+  /// a version of the format that is not supported, or describes a K2Tree whose stems and
+  /// leaves are not whole blocks. Returns a StructureError if the stems and leaves do
+  /// not describe a valid K2Tree, or an Io error if the input ends early.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   assert!(K2Tree::read_from(&b"not a K2Tree"[..]).is_err());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn read_from(mut reader: impl Read) -> Result<Self> {
+    /* The number of stem-layers in the fixed part of the header decides
+    how many k values follow it */
+    let mut header = vec![0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    header.resize(header_len(&header)?, 0);
+    reader.read_exact(&mut header[HEADER_LEN..])?;
+    let fields = parse_header(&header)?;
+    let stems = read_bits(&mut reader, fields.stems_len)?;
+    let leaves = read_bits(&mut reader, fields.leaves_len)?;
+    check_structure(&fields.stem_ks, fields.leaf_k, &stems, &leaves)?;
+    let mut tree = K2Tree::from_bits_with_stem_ks(fields.stem_ks, fields.leaf_k, stems, leaves);
+    let [width, height] = fields.dimensions;
+    tree.set_dimensions(width, height);
+    tree.check_dimensions()?;
+    Ok(tree)
+  }
+}
+
+/* The contents of a valid header */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Header {
+  pub(crate) len: usize,
+  pub(crate) stem_k: usize,
+  pub(crate) leaf_k: usize,
+  pub(crate) max_slayers: usize,
+  pub(crate) stems_len: usize,
+  pub(crate) leaves_len: usize,
+  pub(crate) dimensions: [usize; 2],
+  /* The k value of each stem-layer */
+  pub(crate) stem_ks: Vec<usize>,
+}
+/* The length of the header, including the k value of each stem-layer, from
+at least its fixed part */
+pub(crate) fn header_len(header: &[u8]) -> Result<usize> {
+  let invalid = |reason: &str| Err(Error::InvalidFormat { reason: reason.into() });
+  if header.len() < HEADER_LEN {
+    return invalid("Input is shorter than the header")
+  }
+  if header[0..4] != MAGIC {
+    return invalid("Input does not begin with the magic bytes")
+  }
+  let mut version = [0u8; 4];
+  version.copy_from_slice(&header[4..8]);
+  let version = u32::from_le_bytes(version);
+  if version != VERSION {
+    return Err(Error::InvalidFormat {
+      reason: format!("Unsupported version {}, expected {}", version, VERSION)
+    })
+  }
+  let mut max_slayers = [0u8; 8];
+  max_slayers.copy_from_slice(&header[24..32]);
+  let max_slayers = u64::from_le_bytes(max_slayers);
+  if max_slayers > MAX_SLAYERS as u64 {
+    return invalid("Matrix is too wide for this platform")
+  }
+  Ok(HEADER_LEN + max_slayers as usize * 8)
+}
+pub(crate) fn parse_header(header: &[u8]) -> Result<Header> {
+  let invalid = |reason: &str| Err(Error::InvalidFormat { reason: reason.into() });
+  let len = header_len(header)?;
+  if header.len() < len {
+    return invalid("Input is shorter than the header")
+  }
+  let mut values = vec![0usize; (len - 8) / 8];
+  for (i, value) in values.iter_mut().enumerate() {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&header[8+i*8..16+i*8]);
+    let word = u64::from_le_bytes(word);
+    if word > usize::MAX as u64 {
+      return invalid("Value in header is too large for this platform")
+    }
+    *value = word as usize;
+  }
+  let [stem_k, leaf_k, max_slayers, stems_len, leaves_len, width, height] = [
+    values[0], values[1], values[2], values[3], values[4], values[5], values[6]
+  ];
+  let stem_ks = &values[7..];
+  if let Some(&stem_k) = std::iter::once(&stem_k).chain(stem_ks).find(|&&stem_k| stem_k < 2) {
+    return Err(StructureError::SmallStemKValue { stem_k }.into())
+  }
+  if leaf_k < 2 {
+    return Err(StructureError::SmallLeafKValue { leaf_k }.into())
+  }
+  if max_slayers == 0 {
+    return invalid("K2Tree has no stem-layers")
+  }
+  if checked_matrix_width(stem_ks.iter().copied(), leaf_k).is_none() {
+    return invalid("Matrix is too wide for this platform")
+  }
+  let stem_ks = stem_ks.to_vec();
+  if stem_ks[0] != stem_k {
+    return Err(StructureError::StemKsMismatch { stem_k, max_slayers, stem_ks }.into())
+  }
+  for &k in stem_ks.iter().chain(std::iter::once(&leaf_k)) {
+    block_len(k)?;
+  }
+  let uniform = stem_ks.iter().all(|&k| k == stem_k);
+  if stems_len == 0 || (uniform && stems_len % (stem_k * stem_k) != 0) {
+    return invalid("Stems are not a whole number of blocks")
+  }
+  if leaves_len % (leaf_k * leaf_k) != 0 {
+    return invalid("Leaves are not a whole number of blocks")
+  }
+  Ok(Header {
+    len,
+    stem_k,
+    leaf_k,
+    max_slayers,
+    stems_len,
+    leaves_len,
+    dimensions: [width, height],
+    stem_ks,
+  })
+}
+/* The number of bytes `len` bits take up once packed into u64 words */
+pub(crate) fn packed_len(len: usize) -> usize {
+  len.div_ceil(64) * 8
+}
+fn write_bits(writer: &mut impl Write, bits: &BitVec) -> Result<()> {
+  let mut bytes = Vec::with_capacity(packed_len(bits.len()));
+  for chunk in bits.chunks(64) {
+    let mut word: u64 = 0;
+    for (i, bit) in chunk.iter().enumerate() {
+      if *bit { word |= 1 << i; }
+    }
+    bytes.extend_from_slice(&word.to_le_bytes());
+  }
+  writer.write_all(&bytes)?;
+  Ok(())
+}
+fn read_bits(reader: &mut impl Read, len: usize) -> Result<BitVec> {
+  let mut bits = BitVec::new();
+  let mut word = [0u8; 8];
+  while bits.len() < len {
+    reader.read_exact(&mut word)?;
+    let word = u64::from_le_bytes(word);
+    let word_len = std::cmp::min(64, len - bits.len());
+    if word_len < 64 && word >> word_len != 0 {
+      return Err(Error::InvalidFormat {
+        reason: "Unused bits after the end of the stems or leaves are not 0".into()
+      })
+    }
+    for i in 0..word_len {
+      bits.push(word & (1 << i) != 0);
+    }
+  }
+  Ok(bits)
+}
+
+#[cfg(test)]
+mod api {
+  use super::*;
+  #[test]
+  fn round_trip() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let coords: Vec<(usize, usize)> = (0..150)
+          .map(|_| (rng.gen_range(0, 100), rng.gen_range(0, 100)))
+          .collect();
+        for tree in [K2Tree::from_coords(coords, stem_k, leaf_k)?, K2Tree::with_k(stem_k, leaf_k)?].iter() {
+          let mut bytes = Vec::new();
+          tree.write_to(&mut bytes)?;
+          assert_eq!(
+            HEADER_LEN + 8 * tree.max_slayers + packed_len(tree.stems.len()) + packed_len(tree.leaves.len()),
+            bytes.len()
+          );
+          assert_eq!(*tree, K2Tree::read_from(&bytes[..])?);
+        }
+      }
+    }
+    Ok(())
+  }
+  #[test]
+  fn layout() -> Result<()> {
+    let tree = K2Tree::from_coords(vec![(0, 0), (7, 7)], 2, 2)?;
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes)?;
+    let mut expected = Vec::new();
+    expected.extend_from_slice(b"K2TR");
+    expected.extend_from_slice(&[1, 0, 0, 0]);
+    /* The header, then the k value of each stem-layer */
+    for &value in [2u64, 2, 2, 12, 8, 8, 8, 2, 2].iter() {
+      expected.extend_from_slice(&value.to_le_bytes());
+    }
+    /* Stems 1001 1000 0001, leaves 1000 0001 */
+    expected.extend_from_slice(&[0b0001_1001, 0b1000, 0, 0, 0, 0, 0, 0]);
+    expected.extend_from_slice(&[0b1000_0001, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(expected, bytes);
+    Ok(())
+  }
+  #[test]
+  fn invalid_input() -> Result<()> {
+    let mut bytes = Vec::new();
+    K2Tree::from_coords(vec![(0, 0), (7, 7)], 2, 2)?.write_to(&mut bytes)?;
+    let read = |bytes: &[u8]| K2Tree::read_from(bytes);
+    /* Truncated */
+    assert!(matches!(read(&bytes[..bytes.len()-1]), Err(Error::Io{..})));
+    assert!(matches!(read(&bytes[..20]), Err(Error::Io{..})));
+    /* Bad magic bytes and version */
+    let mut bad = bytes.clone();
+    bad[0] = b'X';
+    assert!(matches!(read(&bad), Err(Error::InvalidFormat{..})));
+    let mut bad = bytes.clone();
+    bad[4] = 2;
+    assert!(matches!(read(&bad), Err(Error::InvalidFormat{..})));
+    /* k value too small */
+    let mut bad = bytes.clone();
+    bad[8] = 1;
+    assert_eq!(
+      Err(Error::from(crate::error::StructureError::SmallStemKValue { stem_k: 1 })),
+      read(&bad)
+    );
+    let mut bad = bytes.clone();
+    bad[HEADER_LEN+8] = 1;
+    assert_eq!(
+      Err(Error::from(crate::error::StructureError::SmallStemKValue { stem_k: 1 })),
+      read(&bad)
+    );
+    let mut bad = bytes.clone();
+    bad[16] = 0;
+    assert_eq!(
+      Err(Error::from(crate::error::StructureError::SmallLeafKValue { leaf_k: 0 })),
+      read(&bad)
+    );
+    /* The root's k value is not the stem_k */
+    let mut bad = bytes.clone();
+    bad[HEADER_LEN] = 3;
+    assert_eq!(
+      Err(Error::from(crate::error::StructureError::StemKsMismatch {
+        stem_k: 2,
+        max_slayers: 2,
+        stem_ks: vec![3, 2],
+      })),
+      read(&bad)
+    );
+    /* More stem-layers than any matrix_width fits */
+    let mut bad = bytes.clone();
+    bad[24] = 65;
+    assert!(matches!(read(&bad), Err(Error::InvalidFormat{..})));
+    /* A stem too large for its length to fit in a usize */
+    let mut bad = bytes.clone();
+    bad[24] = 1;
+    bad[8..16].copy_from_slice(&(1u64 << 33).to_le_bytes());
+    bad[HEADER_LEN..HEADER_LEN+8].copy_from_slice(&(1u64 << 33).to_le_bytes());
+    assert_eq!(
+      Err(Error::from(crate::error::StructureError::OversizedBlock { k: 1 << 33 })),
+      read(&bad)
+    );
+    /* Partial block of stems */
+    let stems_start = HEADER_LEN + 16;
+    let mut bad = bytes.clone();
+    bad[32] = 13;
+    assert!(matches!(read(&bad), Err(Error::InvalidFormat{..})));
+    /* Padding bits set */
+    let mut bad = bytes.clone();
+    bad[stems_start+2] = 1;
+    assert!(matches!(read(&bad), Err(Error::InvalidFormat{..})));
+    /* Stem pointing to a missing leaf */
+    let mut bad = bytes.clone();
+    bad[stems_start+1] |= 0b10;
+    assert!(matches!(read(&bad), Err(Error::StructureError{..})));
+    Ok(())
+  }
+  #[test]
+  fn dimensions() -> Result<()> {
+    let mut tree = K2Tree::with_dimensions(3, 2, 5, 100)?;
+    tree.set_many(vec![(0, 0, true), (4, 99, true), (2, 50, true)])?;
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes)?;
+    let read = K2Tree::read_from(&bytes[..])?;
+    assert_eq!([5, 100], [read.width(), read.height()]);
+    assert_eq!(tree, read);
+    /* Width larger than the matrix_width */
+    let mut bad = bytes.clone();
+    bad[48..56].copy_from_slice(&1000u64.to_le_bytes());
+    assert_eq!(
+      Err(Error::from(crate::error::StructureError::OversizedDimensions {
+        width: 1000,
+        height: 100,
+        matrix_width: 162,
+      })),
+      K2Tree::read_from(&bad[..])
+    );
+    /* Height that leaves a 1 outside of it */
+    let mut bad = bytes;
+    bad[56..64].copy_from_slice(&99u64.to_le_bytes());
+    assert_eq!(
+      Err(Error::from(crate::error::StructureError::OneOutsideDimensions { x_y: [4, 99] })),
+      K2Tree::read_from(&bad[..])
+    );
+    Ok(())
+  }
+  #[test]
+  fn stem_ks() -> Result<()> {
+    let mut tree = K2Tree::with_stem_ks(&[4, 3, 2], 2)?;
+    for &(x, y) in [(0, 0), (47, 47), (13, 30), (40, 2), (5, 44)].iter() {
+      tree.set(x, y, true)?;
+    }
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes)?;
+    for (i, &stem_k) in [4u64, 3, 2].iter().enumerate() {
+      assert_eq!(stem_k.to_le_bytes(), bytes[HEADER_LEN+i*8..HEADER_LEN+i*8+8]);
+    }
+    let read = K2Tree::read_from(&bytes[..])?;
+    assert_eq!(vec![4, 3, 2], read.stem_ks());
+    assert_eq!(tree, read);
+    Ok(())
+  }
+}
@@ -0,0 +1,299 @@
+use bitvec::prelude::{bitvec, BitVec};
+use crate::error::K2TreeError as Error;
+use crate::tree::datastore::K2Tree;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Builds a K2Tree one 1 at a time from coordinates that arrive in order.
+///
+/// Each stem-layer of a K2Tree is stored in the order a depth-first traversal
+/// of the tree would visit it, so as long as the coordinates of the 1s are pushed
+/// in that same order every layer is only ever appended to, and only the path
+/// from the root to the latest 1 needs to be remembered.
+///
+/// That order is a Z-order (Morton order) generalised to `stem_k` and `leaf_k`:
+/// the matrix is split into `stem_k * stem_k` sub-matrices, which are visited
+/// row by row, and every sub-matrix is itself visited the same way until the
+/// sub-matrices are the size of a leaf, whose bits are visited row by row.
+/// `z_order` gives the position of any coordinate in this order, and `sort_z_order`
+/// sorts coordinates into it, so input can be put in order ahead of time.
+///
+/// Only this order is accepted: coordinates in row-major order, or any other, are
+/// rejected with an UnorderedInput error as soon as one comes before the last.
+/// `K2Tree::from_coords` builds a K2Tree from coordinates in any order.
+///
+/// Pushing the same coordinates more than once in a row has no effect.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::tree::K2TreeBuilder;
+///   let mut builder = K2TreeBuilder::new(2, 2, 8)?;
+///   for &(x, y) in [(0, 0), (1, 1), (3, 0), (0, 3), (5, 1), (0, 4), (7, 7)].iter() {
+///     builder.push(x, y)?;
+///   }
+///   /* (4, 4) comes before (7, 7) */
+///   assert!(builder.push(4, 4).is_err());
+///   let tree = builder.finish();
+///   assert_eq!(8, tree.matrix_width());
+///   assert_eq!(7, tree.count_ones());
+///   assert_eq!(true, tree.get(5, 1)?);
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct K2TreeBuilder {
+  /* The k value of each stem-layer from the root down */
+  stem_ks: Vec<usize>,
+  leaf_k: usize,
+  max_slayers: usize,
+  /* The stems of each layer, built separately then joined at the end */
+  layers: Vec<BitVec>,
+  leaves: BitVec,
+  /* The child chosen at each stem-layer on the way to the latest 1 */
+  path: Vec<usize>,
+  last_key: Option<u128>,
+}
+impl K2TreeBuilder {
+  /// Returns a K2TreeBuilder for a K2Tree with the specified k-values, whose
+  /// matrix is the smallest one that is at least `width` bits wide and no smaller
+  /// than that of `K2Tree::with_k(stem_k, leaf_k)`.
+  ///
+  /// Returns a SmallKValue error if either k < 2, or an OversizedMatrix error if
+  /// no matrix_width that fits in a usize is at least `width`.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::tree::K2TreeBuilder;
+  ///   assert_eq!(8, K2TreeBuilder::new(2, 2, 0)?.matrix_width());
+  ///   assert_eq!(16, K2TreeBuilder::new(2, 2, 9)?.matrix_width());
+  ///   assert!(K2TreeBuilder::new(1, 2, 8).is_err());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn new(stem_k: usize, leaf_k: usize, width: usize) -> Result<Self> {
+    let max_slayers = K2Tree::slayers_to_fit(width.saturating_sub(1), stem_k, leaf_k)?;
+    Ok(K2TreeBuilder::with_slayers(stem_k, leaf_k, max_slayers))
+  }
+  /// Returns the width of the matrix the built K2Tree will represent.
+  pub fn matrix_width(&self) -> usize {
+    self.leaf_k * self.stem_ks.iter().product::<usize>()
+  }
+  /// Returns the position of the coordinates (x, y) in the order the
+  /// K2TreeBuilder requires them to be pushed in.
+  ///
+  /// The result is meaningless for coordinates outside of the matrix.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::tree::K2TreeBuilder;
+  ///   let builder = K2TreeBuilder::new(2, 2, 8)?;
+  ///   let mut coords = vec![(7, 7), (0, 4), (5, 1), (0, 0)];
+  ///   coords.sort_by_key(|&(x, y)| builder.z_order(x, y));
+  ///   assert_eq!(vec![(0, 0), (5, 1), (0, 4), (7, 7)], coords);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn z_order(&self, x: usize, y: usize) -> u128 {
+    let leaf_len = (self.leaf_k * self.leaf_k) as u128;
+    let mut key: u128 = 0;
+    let mut sub_width = self.matrix_width();
+    for &stem_k in self.stem_ks.iter() {
+      sub_width /= stem_k;
+      let child_pos = ((y / sub_width) % stem_k) * stem_k + ((x / sub_width) % stem_k);
+      key = key * (stem_k * stem_k) as u128 + child_pos as u128;
+    }
+    let leaf_offset = (y % self.leaf_k) * self.leaf_k + (x % self.leaf_k);
+    key * leaf_len + leaf_offset as u128
+  }
+  /// Sorts `coords` into the order the K2TreeBuilder requires them to be pushed in.
+  ///
+  /// The order is meaningless for coordinates outside of the matrix.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::tree::K2TreeBuilder;
+  ///   let mut builder = K2TreeBuilder::new(2, 2, 8)?;
+  ///   /* Row-major order */
+  ///   let mut coords = vec![(4, 0), (1, 1), (0, 2), (6, 6)];
+  ///   assert!(coords.iter().try_for_each(|&(x, y)| builder.push(x, y)).is_err());
+  ///   let mut builder = K2TreeBuilder::new(2, 2, 8)?;
+  ///   builder.sort_z_order(&mut coords);
+  ///   assert_eq!(vec![(1, 1), (0, 2), (4, 0), (6, 6)], coords);
+  ///   for &(x, y) in coords.iter() {
+  ///     builder.push(x, y)?;
+  ///   }
+  ///   assert_eq!(4, builder.finish().count_ones());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn sort_z_order(&self, coords: &mut [(usize, usize)]) {
+    coords.sort_unstable_by_key(|&(x, y)| self.z_order(x, y));
+  }
+  /// Adds a 1 at the coordinates (x, y).
+  ///
+  /// The coordinates must be pushed in the order given by `z_order`, not row by row.
+  ///
+  /// Returns an OutOfBounds error if (x, y) is outside of the matrix, or an
+  /// UnorderedInput error if (x, y) comes before the previously pushed coordinates.
+  /// The K2TreeBuilder is left unchanged by either error.
+  pub fn push(&mut self, x: usize, y: usize) -> Result<()> {
+    let matrix_width = self.matrix_width();
+    if x >= matrix_width || y >= matrix_width {
+      return Err(Error::Write {
+        source: Box::new(Error::OutOfBounds {
+          x_y: [x, y],
+          min_x_y: [0, 0],
+          max_x_y: [matrix_width-1; 2]
+        })
+      })
+    }
+    let key = self.z_order(x, y);
+    if let Some(last_key) = self.last_key {
+      if key == last_key { return Ok(()) }
+      if key < last_key {
+        return Err(Error::Write {
+          source: Box::new(Error::UnorderedInput {
+            x_y: [x, y],
+            prev_x_y: self.last_coords(),
+          })
+        })
+      }
+    }
+    self.push_key(key);
+    Ok(())
+  }
+  /// Consumes the K2TreeBuilder, returning the K2Tree it built.
+  pub fn finish(self) -> K2Tree {
+    let mut stems = BitVec::new();
+    for layer in self.layers.into_iter().filter(|layer| !layer.is_empty()) {
+      stems.extend(layer);
+    }
+    K2Tree::from_bits_with_stem_ks(self.stem_ks, self.leaf_k, stems, self.leaves)
+  }
+}
+
+/* Private */
+impl K2TreeBuilder {
+  pub(crate) fn with_slayers(stem_k: usize, leaf_k: usize, max_slayers: usize) -> Self {
+    K2TreeBuilder::with_stem_ks(vec![stem_k; max_slayers], leaf_k)
+  }
+  pub(crate) fn with_stem_ks(stem_ks: Vec<usize>, leaf_k: usize) -> Self {
+    let max_slayers = stem_ks.len();
+    let mut layers = vec![BitVec::new(); max_slayers];
+    layers[0] = bitvec![0; stem_ks[0]*stem_ks[0]];
+    K2TreeBuilder {
+      stem_ks,
+      leaf_k,
+      max_slayers,
+      layers,
+      leaves: BitVec::new(),
+      path: vec![0; max_slayers],
+      last_key: None,
+    }
+  }
+  /* Adds a 1 by its key, keys must be pushed in increasing order */
+  pub(crate) fn push_key(&mut self, key: u128) {
+    let leaf_len = (self.leaf_k * self.leaf_k) as u128;
+    let leaf_offset = (key % leaf_len) as usize;
+    let mut path = vec![0; self.max_slayers];
+    let mut rest = key / leaf_len;
+    for layer in (0..self.max_slayers).rev() {
+      let stem_len = (self.stem_ks[layer] * self.stem_ks[layer]) as u128;
+      path[layer] = (rest % stem_len) as usize;
+      rest /= stem_len;
+    }
+    /* Find the first layer where the path leaves the previous one,
+    every stem below it (and the leaf) is new */
+    let diverge = match self.last_key {
+      None => 0,
+      Some(_) => (0..self.max_slayers)
+        .find(|&layer| path[layer] != self.path[layer])
+        .unwrap_or(self.max_slayers),
+    };
+    for layer in diverge+1..self.max_slayers {
+      let stem_len = self.stem_ks[layer] * self.stem_ks[layer];
+      let new_len = self.layers[layer].len() + stem_len;
+      self.layers[layer].resize(new_len, false);
+    }
+    if diverge < self.max_slayers {
+      let new_len = self.leaves.len() + leaf_len as usize;
+      self.leaves.resize(new_len, false);
+    }
+    /* Set the bits along the path in the latest stem of each layer */
+    for (layer, &child_pos) in path.iter().enumerate() {
+      let stem_start = self.layers[layer].len() - self.stem_ks[layer] * self.stem_ks[layer];
+      self.layers[layer].set(stem_start + child_pos, true);
+    }
+    let leaf_start = self.leaves.len() - leaf_len as usize;
+    self.leaves.set(leaf_start + leaf_offset, true);
+    self.path = path;
+    self.last_key = Some(key);
+  }
+  /* The coordinates of the latest 1, rebuilt from the path taken to it */
+  fn last_coords(&self) -> [usize; 2] {
+    let leaf_len = (self.leaf_k * self.leaf_k) as u128;
+    let leaf_offset = (self.last_key.unwrap_or(0) % leaf_len) as usize;
+    let mut x = leaf_offset % self.leaf_k;
+    let mut y = leaf_offset / self.leaf_k;
+    let mut sub_width = self.leaf_k;
+    for (&child_pos, &stem_k) in self.path.iter().zip(self.stem_ks.iter()).rev() {
+      x += (child_pos % stem_k) * sub_width;
+      y += (child_pos / stem_k) * sub_width;
+      sub_width *= stem_k;
+    }
+    [x, y]
+  }
+}
+
+#[cfg(test)]
+mod api {
+  use super::*;
+  #[test]
+  fn push_in_order() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let mut coords: Vec<(usize, usize)> = (0..200)
+          .map(|_| (rng.gen_range(0, 100), rng.gen_range(0, 100)))
+          .collect();
+        let expected = K2Tree::from_coords(coords.clone(), stem_k, leaf_k)?;
+        let mut builder = K2TreeBuilder::new(stem_k, leaf_k, 100)?;
+        coords.sort_by_key(|&(x, y)| builder.z_order(x, y));
+        for (x, y) in coords {
+          builder.push(x, y)?;
+        }
+        assert_eq!(expected, builder.finish());
+      }
+    }
+    Ok(())
+  }
+  #[test]
+  fn push_out_of_order() -> Result<()> {
+    let mut builder = K2TreeBuilder::new(3, 2, 18)?;
+    builder.push(10, 11)?;
+    assert_eq!(
+      Err(Error::Write {
+        source: Box::new(Error::UnorderedInput {
+          x_y: [0, 0],
+          prev_x_y: [10, 11],
+        })
+      }),
+      builder.push(0, 0)
+    );
+    assert!(builder.push(18, 0).is_err());
+    builder.push(10, 11)?;
+    builder.push(17, 17)?;
+    let tree = builder.finish();
+    assert_eq!(2, tree.count_ones());
+    assert!(tree.get(10, 11)?);
+    assert!(tree.get(17, 17)?);
+    Ok(())
+  }
+  #[test]
+  fn finish_empty() -> Result<()> {
+    for stem_k in 2..9 {
+      for leaf_k in 2..9 {
+        let tree = K2TreeBuilder::new(stem_k, leaf_k, 0)?.finish();
+        assert_eq!(K2Tree::with_k(stem_k, leaf_k)?, tree);
+      }
+    }
+    Ok(())
+  }
+}
@@ -1,6 +1,6 @@
 use {
-  std::fmt,
-  bitvec::{prelude::{bitvec, bitbox, BitVec}},
+  std::{fmt, ops::RangeInclusive},
+  bitvec::{prelude::{bitvec, bitbox, BitSlice, BitVec}},
   serde::{
     Serialize,
     Deserialize,
@@ -9,7 +9,7 @@ use {
     ser::SerializeStruct,
     de::{self, Visitor, MapAccess}
   },
-  crate::error::K2TreeError as Error,
+  crate::error::{K2TreeError as Error, StructureError},
   crate::tree::*,
   crate::matrix::BitMatrix,
 };
@@ -19,9 +19,15 @@ type Result<T> = std::result::Result<T, Error>;
 /// A collection designed to efficiently compress sparsely-populated bit-matrices.
 ///
 /// The `K2Tree` represents a matrix of bits and behaves ***as if*** it is a bit-matrix.
-/// The `k` value of this structure is currently fixed at 2, but future updates may allow customisation.
-/// The matrix represented by the `K2Tree` must always be square, with a width/height equal to a power of k: 8, 16, 32 etc.
+/// Its stems and leaves each have their own `k` value, and each stem-layer can have its own
+/// `k` value too when made with `with_stem_ks`.
+/// The stems and leaves always cover a square matrix, whose matrix_width is the leaf_k multiplied
+/// by the k value of every stem-layer: 8, 16, 32 etc. for k values of 2. The matrix the `K2Tree`
+/// represents can have a smaller width and height than that, as with `with_dimensions`.
 /// This isn't much of an issue because almost all empty cells in the matrix are compressed-away, so don't stress about wasted columns/rows.
+///
+/// Navigating the tree relies on an index built over the stems and leaves. If the `stems`,
+/// `leaves` or `max_slayers` fields are modified directly, call `reindex` before using the `K2Tree` again.
 /// 
 /// ```
 /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
@@ -39,7 +45,9 @@ type Result<T> = std::result::Result<T, Error>;
 /// ```
 #[derive(Debug, Clone)]
 pub struct K2Tree {
-  /// The k value of the K2Tree's stems.
+  /// The k value of the root stem-layer, which is the k value of every stem-layer
+  /// unless the K2Tree was made with `with_stem_ks`. Use `stem_ks` for the k value
+  /// of each stem-layer.
   pub stem_k: usize,
   /// The k value of the K2Tree's leaves.
   pub leaf_k: usize,
@@ -49,6 +57,16 @@ pub struct K2Tree {
   pub stems: BitVec,
   /// The bits that comprise the leaves of the tree.
   pub leaves: BitVec,
+  /// Rank/select directory over `stems`, used to navigate the tree.
+  pub(crate) stem_ranks: RankSelect,
+  /// Rank/select directory over `leaves`, used to count their 1s.
+  pub(crate) leaf_ranks: RankSelect,
+  /// The index in `stems` at which each stem-layer begins.
+  pub(crate) slayer_starts: Vec<usize>,
+  /// The width and height of the matrix, if they are not both the matrix_width.
+  pub(crate) dimensions: Option<[usize; 2]>,
+  /// The k value of each stem-layer from the root down, if they are not all the stem_k.
+  pub(crate) stem_ks: Option<Vec<usize>>,
 }
 
 /* Public */
@@ -63,13 +81,7 @@ impl K2Tree {
   /// assert_eq!(2, tree.leaf_k);
   /// ```
   pub fn new() -> Self {
-    K2Tree {
-      stem_k: 2,
-      leaf_k: 2,
-      max_slayers: 2,
-      stems: bitvec![0; 4],
-      leaves: BitVec::new(),
-    }
+    K2Tree::from_bits(2, 2, 2, bitvec![0; 4], BitVec::new())
   }
   /// Returns a `K2Tree` with a specified k-value, which represents an empty bit-matrix
   /// of width `k.pow(3)`.
@@ -94,16 +106,145 @@ impl K2Tree {
     else if leaf_k < 2 {
       return Err(Error::SmallLeafKValue { leaf_k: leaf_k as u8 })
     }
-    Ok(K2Tree {
+    Ok(K2Tree::from_bits(
+      stem_k,
+      leaf_k,
+      2,
+      bitvec![0; stem_k*stem_k],
+      BitVec::new()
+    ))
+  }
+  /// Returns a `K2Tree` with the specified k values, which represents an empty
+  /// bit-matrix of exactly `width` by `height` bits.
+  /// 
+  /// The K2Tree still covers a square matrix of `matrix_width` bits, which is
+  /// the smallest that fits both dimensions, but coordinates outside of `width`
+  /// and `height` are out of bounds. Empty parts of the matrix take up no space,
+  /// so a matrix much wider than it is tall costs no more than its 1s.
+  /// 
+  /// Returns a SmallKValue error if either k < 2.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_dimensions(2, 2, 3, 1000)?;
+  ///   assert_eq!(3, tree.width());
+  ///   assert_eq!(1000, tree.height());
+  ///   assert_eq!(1024, tree.matrix_width());
+  ///   tree.set(2, 999, true)?;
+  ///   assert!(tree.set(3, 0, true).is_err());
+  ///   Ok(())
+  /// }
+  /// ``` 
+  pub fn with_dimensions(stem_k: usize, leaf_k: usize, width: usize, height: usize) -> Result<Self> {
+    let max_slayers = K2Tree::slayers_to_fit(std::cmp::max(width, height).saturating_sub(1), stem_k, leaf_k)?;
+    let mut tree = K2Tree::from_bits(
+      stem_k,
+      leaf_k,
+      max_slayers,
+      bitvec![0; stem_k*stem_k],
+      BitVec::new()
+    );
+    tree.set_dimensions(width, height);
+    Ok(tree)
+  }
+  /// Returns a `K2Tree` with a k value for each of its stem-layers, which represents
+  /// an empty bit-matrix.
+  /// 
+  /// `stem_ks` gives the k value of each stem-layer from the root down, so the
+  /// matrix_width is `leaf_k` multiplied by all of them. This is the hybrid
+  /// variant of the K2Tree, which uses larger k values near the root to make
+  /// the tree shallower and smaller ones further down so that sparse parts of
+  /// the matrix take up less space. The `stem_k` is the k value of the root.
+  /// 
+  /// Returns a SmallKValue error if any k < 2, a NoStemLayers StructureError
+  /// if `stem_ks` is empty, or an OversizedMatrix error if the matrix_width does
+  /// not fit in a usize.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_stem_ks(&[4, 2, 2], 2)?;
+  ///   assert_eq!(32, tree.matrix_width());
+  ///   assert_eq!(vec![4, 2, 2], tree.stem_ks());
+  ///   assert_eq!(4, tree.stem_k);
+  ///   tree.set(27, 30, true)?;
+  ///   assert_eq!(true, tree.get(27, 30)?);
+  ///   Ok(())
+  /// }
+  /// ``` 
+  pub fn with_stem_ks(stem_ks: &[usize], leaf_k: usize) -> Result<Self> {
+    if let Some(&stem_k) = stem_ks.iter().find(|&&stem_k| stem_k < 2) {
+      return Err(Error::SmallStemKValue { stem_k: stem_k as u8 })
+    }
+    else if leaf_k < 2 {
+      return Err(Error::SmallLeafKValue { leaf_k: leaf_k as u8 })
+    }
+    else if stem_ks.is_empty() {
+      return Err(StructureError::NoStemLayers.into())
+    }
+    else if validate::checked_matrix_width(stem_ks.iter().copied(), leaf_k).is_none() {
+      return Err(Error::OversizedMatrix { stem_ks: stem_ks.to_vec(), leaf_k })
+    }
+    Ok(K2Tree::from_bits_with_stem_ks(
+      stem_ks.to_vec(),
+      leaf_k,
+      bitvec![0; stem_ks[0]*stem_ks[0]],
+      BitVec::new()
+    ))
+  }
+  /// Returns a `K2Tree` built directly from its raw parts.
+  /// 
+  /// No checks are made that the parts describe a valid `K2Tree`.
+  /// ```
+  /// use bitvec::prelude::bitvec;
+  /// use k2_tree::K2Tree;
+  /// let tree = K2Tree::from_bits(
+  ///   2,
+  ///   2,
+  ///   2,
+  ///   bitvec![0,1,1,1, 1,1,0,1, 1,0,0,0, 1,0,0,0],
+  ///   bitvec![0,1,1,0, 0,1,0,1, 1,1,0,0, 1,0,0,0, 0,1,1,0],
+  /// );
+  /// assert_eq!(true, tree.get(5, 0).unwrap());
+  /// assert_eq!(false, tree.get(0, 0).unwrap());
+  /// ```
+  pub fn from_bits(stem_k: usize, leaf_k: usize, max_slayers: usize, stems: BitVec, leaves: BitVec) -> Self {
+    let mut tree = K2Tree {
       stem_k,
       leaf_k,
-      max_slayers: 2,
-      stems: bitvec![0; stem_k*stem_k],
-      leaves: BitVec::new(),
-    })
+      max_slayers,
+      stems,
+      leaves,
+      stem_ranks: RankSelect::default(),
+      leaf_ranks: RankSelect::default(),
+      slayer_starts: Vec::new(),
+      dimensions: None,
+      stem_ks: None,
+    };
+    tree.reindex();
+    tree
+  }
+  /// Rebuilds the index used to navigate the `K2Tree`.
+  /// 
+  /// Every method on `K2Tree` keeps the index up to date, so this only needs to be
+  /// called after modifying the `stems`, `leaves` or `max_slayers` fields directly.
+  /// ```
+  /// use bitvec::prelude::bitvec;
+  /// use k2_tree::K2Tree;
+  /// let mut tree = K2Tree::new();
+  /// tree.stems = bitvec![1,0,0,0, 1,0,0,0];
+  /// tree.leaves = bitvec![1,0,0,0];
+  /// tree.reindex();
+  /// assert_eq!(true, tree.get(0, 0).unwrap());
+  /// assert_eq!(1, tree.count_ones());
+  /// ```
+  pub fn reindex(&mut self) {
+    self.rank_stems();
+    self.leaf_ranks = RankSelect::new(&self.leaves);
+    self.slayer_starts = self.build_layer_starts();
   }
   /// Changes the stem_k value of a `K2Tree`. This can be a time and space expensive operation
   /// for large, non-sparse datasets.
+  /// A K2Tree with a k value for each stem-layer is given `stem_k` for all of them.
   /// Returns a SmallKValue error if stem_k < 2.
   /// ```
   /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
@@ -115,15 +256,19 @@ impl K2Tree {
   /// }
   /// ``` 
   pub fn set_stem_k(&mut self, stem_k: usize) -> Result<()> {
-    if self.stem_k == stem_k { return Ok(()) }
+    if self.stem_k == stem_k && self.stem_ks.is_none() { return Ok(()) }
     if stem_k < 2 {
       return Err(Error::SmallStemKValue{stem_k: stem_k as u8})
     }
+    let dimensions = self.dimensions;
     *self = K2Tree::from_matrix(self.to_matrix()?, stem_k, self.leaf_k)?;
+    if dimensions.is_none() { self.dimensions = None; }
     Ok(())
   }
   /// Changes the leaf_k value of a `K2Tree`. This can be a time and space expensive operation
   /// for large, non-sparse datasets.
+  /// A K2Tree with a k value for each stem-layer keeps them, with stem-layers added
+  /// above the root if the matrix no longer fits.
   /// Returns a SmallKValue error if stem_k < 2.
   /// ```
   /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
@@ -139,7 +284,19 @@ impl K2Tree {
     if leaf_k < 2 {
       return Err(Error::SmallLeafKValue{leaf_k: leaf_k as u8})
     }
-    *self = K2Tree::from_matrix(self.to_matrix()?, self.stem_k, leaf_k)?;
+    let dimensions = self.dimensions;
+    *self = match self.stem_ks {
+      None => K2Tree::from_matrix(self.to_matrix()?, self.stem_k, leaf_k)?,
+      Some(_) => {
+        let [width, height] = [self.width(), self.height()];
+        let stem_ks = self.stem_ks_to_fit(leaf_k, std::cmp::max(width, height).saturating_sub(1))?;
+        let coords = self.leaves().filter(|bit| bit.value).map(|bit| (bit.x, bit.y)).collect();
+        let mut tree = K2Tree::build(coords, stem_ks, leaf_k);
+        tree.set_dimensions(width, height);
+        tree
+      },
+    };
+    if dimensions.is_none() { self.dimensions = None; }
     Ok(())
   }
   ///Returns true if a `K2Tree` contains no 1s.
@@ -159,16 +316,12 @@ impl K2Tree {
   /// }
   /// ```
   pub fn get(&self, x: usize, y: usize) -> Result<bool> {
-    let matrix_width = self.matrix_width();
-    if x >= matrix_width || y >= matrix_width {
+    if x >= self.width() || y >= self.height() {
       return Err(Error::Read {
-        source: Box::new(Error::OutOfBounds {
-          x_y: [x, y],
-          min_x_y: [0, 0],
-          max_x_y: [matrix_width-1; 2]
-        })
+        source: Box::new(self.out_of_bounds([x, y]))
       })
     }
+    let matrix_width = self.matrix_width();
     let descend_result = match self.matrix_bit(x, y, matrix_width) {
       Ok(dr) => dr,
       Err(e) => return Err(Error::Read {
@@ -189,7 +342,7 @@ impl K2Tree {
         let offset = (self.leaf_k * (y - leaf_range.min_y)) + (x - leaf_range.min_x);
         Ok(self.leaves[leaf_start+offset])
       },
-      DescendResult::Stem(_, _) => Ok(false),
+      DescendResult::Stem(..) => Ok(false),
     }
   }
   /// Returns a BitVec containing the bits in a specified row, in order.
@@ -209,16 +362,12 @@ impl K2Tree {
   /// }
   /// ```
   pub fn get_row(&self, y: usize) -> Result<Vec<bool>> {
-    let matrix_width = self.matrix_width();
-    if y >= matrix_width {
+    if y >= self.height() {
       return Err(Error::Read {
-        source: Box::new(Error::OutOfBounds {
-          x_y: [0, y],
-          min_x_y: [0, 0],
-          max_x_y: [matrix_width-1; 2]
-        })
+        source: Box::new(self.out_of_bounds([0, y]))
       })
     }
+    let matrix_width = self.matrix_width();
     let mut ret_v = Vec::new();
     for x in (0..matrix_width).step_by(self.leaf_k) {
       let descend_result = match self.matrix_bit(x, y, matrix_width) {
@@ -239,11 +388,12 @@ impl K2Tree {
           let offset = (self.leaf_k * (y - leaf_range.min_y)) + (x - leaf_range.min_x);
           for i in 0..self.leaf_k { ret_v.push(self.leaves[leaf_start+offset+i]); }
         },
-        DescendResult::Stem(_, _) => {
+        DescendResult::Stem(..) => {
           for _ in 0..self.leaf_k { ret_v.push(false); }
         },
       }
     };
+    ret_v.truncate(self.width());
     Ok(ret_v)
   }
   /// Returns a BitVec containing the bits in a specified column, in order.
@@ -263,16 +413,12 @@ impl K2Tree {
   /// }
   /// ```
   pub fn get_column(&self, x: usize) -> Result<Vec<bool>> {
-    let matrix_width = self.matrix_width();
-    if x >= matrix_width {
+    if x >= self.width() {
       return Err(Error::Read {
-        source: Box::new(Error::OutOfBounds {
-          x_y: [x, 0],
-          min_x_y: [0, 0],
-          max_x_y: [matrix_width-1; 2]
-        })
+        source: Box::new(self.out_of_bounds([x, 0]))
       })
     }
+    let matrix_width = self.matrix_width();
     let mut ret_v = Vec::new();
     for y in (0..matrix_width).step_by(self.leaf_k) {
       let descend_result = match self.matrix_bit(x, y, matrix_width) {
@@ -292,13 +438,208 @@ impl K2Tree {
           let offset = (self.leaf_k * (y - leaf_range.min_y)) + (x - leaf_range.min_x);
           for i in 0..self.leaf_k { ret_v.push(self.leaves[leaf_start+offset+(i*self.leaf_k)]); }
         },
-        DescendResult::Stem(_, _) => {
+        DescendResult::Stem(..) => {
           for _ in 0..self.leaf_k { ret_v.push(false); }
         },
       }
     };
+    ret_v.truncate(self.height());
     Ok(ret_v)
   }
+  /// Returns the x-coordinates of all the 1s in a specified row, in order.
+  /// 
+  /// Unlike `get_row`, only the parts of the K2Tree which contain 1s in the row are visited.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   tree.set(1, 0, true)?;
+  ///   tree.set(3, 0, true)?;
+  ///   tree.set(6, 0, true)?;
+  ///   tree.set(6, 1, true)?;
+  ///   assert_eq!(vec![1, 3, 6], tree.successors(0)?);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn successors(&self, y: usize) -> Result<Vec<usize>> {
+    Ok(self.iter_successors(y)?.collect())
+  }
+  /// Returns an iterator over the x-coordinates of all the 1s in a specified row, in order.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   tree.set(1, 4, true)?;
+  ///   tree.set(7, 4, true)?;
+  ///   let mut successors = tree.iter_successors(4)?;
+  ///   assert_eq!(Some(1), successors.next());
+  ///   assert_eq!(Some(7), successors.next());
+  ///   assert_eq!(None, successors.next());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn iter_successors(&self, y: usize) -> Result<iterators::Successors<'_>> {
+    if y >= self.height() {
+      return Err(Error::Read {
+        source: Box::new(self.out_of_bounds([0, y]))
+      })
+    }
+    Ok(iterators::Successors::new(self, y))
+  }
+  /// Returns the y-coordinates of all the 1s in a specified column, in order.
+  /// 
+  /// Unlike `get_column`, only the parts of the K2Tree which contain 1s in the column are visited.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   tree.set(0, 1, true)?;
+  ///   tree.set(0, 3, true)?;
+  ///   tree.set(0, 6, true)?;
+  ///   tree.set(1, 6, true)?;
+  ///   assert_eq!(vec![1, 3, 6], tree.predecessors(0)?);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn predecessors(&self, x: usize) -> Result<Vec<usize>> {
+    Ok(self.iter_predecessors(x)?.collect())
+  }
+  /// Returns an iterator over the y-coordinates of all the 1s in a specified column, in order.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   tree.set(4, 1, true)?;
+  ///   tree.set(4, 7, true)?;
+  ///   let mut predecessors = tree.iter_predecessors(4)?;
+  ///   assert_eq!(Some(1), predecessors.next());
+  ///   assert_eq!(Some(7), predecessors.next());
+  ///   assert_eq!(None, predecessors.next());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn iter_predecessors(&self, x: usize) -> Result<iterators::Predecessors<'_>> {
+    if x >= self.width() {
+      return Err(Error::Read {
+        source: Box::new(self.out_of_bounds([x, 0]))
+      })
+    }
+    Ok(iterators::Predecessors::new(self, x))
+  }
+  /// Returns the coordinates of all the 1s inside the range `[x.start()..=x.end()] × [y.start()..=y.end()]`
+  /// of the bit-matrix, as (x, y) pairs in row-major order: sorted by y, then by x.
+  /// 
+  /// Only the parts of the K2Tree which overlap the range are visited.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   tree.set(1, 1, true)?;
+  ///   tree.set(2, 3, true)?;
+  ///   tree.set(6, 3, true)?;
+  ///   tree.set(2, 7, true)?;
+  ///   tree.set(4, 0, true)?;
+  ///   assert_eq!(vec![(4, 0), (1, 1), (2, 3)], tree.ones_in_range(0..=4, 0..=4)?);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn ones_in_range(&self, x: RangeInclusive<usize>, y: RangeInclusive<usize>) -> Result<Vec<(usize, usize)>> {
+    let mut ones: Vec<(usize, usize)> = self.iter_ones_in_range(x, y)?.collect();
+    ones.sort_unstable_by_key(|&(x, y)| (y, x));
+    Ok(ones)
+  }
+  /// Returns an iterator over the coordinates of all the 1s inside the range
+  /// `[x.start()..=x.end()] × [y.start()..=y.end()]` of the bit-matrix, as (x, y) pairs.
+  /// 
+  /// The 1s are produced in the order the K2Tree stores them, which is the order
+  /// `K2TreeBuilder::z_order` gives rather than row-major order. Use `ones_in_range`
+  /// for them in row-major order.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   tree.set(5, 5, true)?;
+  ///   tree.set(7, 7, true)?;
+  ///   let mut ones = tree.iter_ones_in_range(4..=7, 4..=6)?;
+  ///   assert_eq!(Some((5, 5)), ones.next());
+  ///   assert_eq!(None, ones.next());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn iter_ones_in_range(&self, x: RangeInclusive<usize>, y: RangeInclusive<usize>) -> Result<iterators::RangeOnes<'_>> {
+    self.check_range(&x, &y)?;
+    Ok(iterators::RangeOnes::new(self, *x.start(), *x.end(), *y.start(), *y.end()))
+  }
+  /// Returns true if there are any 1s inside the range `[x.start()..=x.end()] × [y.start()..=y.end()]`
+  /// of the bit-matrix.
+  /// 
+  /// Stops as soon as a part of the K2Tree containing a 1 is found entirely inside the range.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   tree.set(6, 1, true)?;
+  ///   assert!(tree.any_in_range(4..=7, 0..=3)?);
+  ///   assert!(!tree.any_in_range(0..=5, 0..=7)?);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn any_in_range(&self, x: RangeInclusive<usize>, y: RangeInclusive<usize>) -> Result<bool> {
+    self.check_range(&x, &y)?;
+    if x.is_empty() || y.is_empty() { return Ok(false) }
+    let query = Range2D::new(*x.start(), *x.end(), *y.start(), *y.end());
+    let range_max = self.matrix_width()-1;
+    match self.any_in(0, 0, Range2D::new(0, range_max, 0, range_max), &query) {
+      Ok(found) => Ok(found),
+      Err(e) => Err(Error::Read {
+        source: Box::new(e)
+      }),
+    }
+  }
+  /// Returns the number of 1s in the bit-matrix.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   assert_eq!(0, tree.count_ones());
+  ///   tree.set(1, 1, true)?;
+  ///   tree.set(6, 3, true)?;
+  ///   assert_eq!(2, tree.count_ones());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn count_ones(&self) -> usize {
+    self.leaf_ranks.ones()
+  }
+  /// Returns the number of 1s inside the range `[x.start()..=x.end()] × [y.start()..=y.end()]`
+  /// of the bit-matrix.
+  /// 
+  /// Parts of the K2Tree which lie entirely inside the range are counted without being visited.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   tree.set(1, 1, true)?;
+  ///   tree.set(2, 3, true)?;
+  ///   tree.set(6, 3, true)?;
+  ///   tree.set(2, 7, true)?;
+  ///   assert_eq!(2, tree.count_in_range(0..=4, 0..=4)?);
+  ///   assert_eq!(4, tree.count_in_range(0..=7, 0..=7)?);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn count_in_range(&self, x: RangeInclusive<usize>, y: RangeInclusive<usize>) -> Result<usize> {
+    self.check_range(&x, &y)?;
+    if x.is_empty() || y.is_empty() { return Ok(0) }
+    let query = Range2D::new(*x.start(), *x.end(), *y.start(), *y.end());
+    let range_max = self.matrix_width()-1;
+    match self.count_in(0, 0, Range2D::new(0, range_max, 0, range_max), &query) {
+      Ok(count) => Ok(count),
+      Err(e) => Err(Error::Read {
+        source: Box::new(e)
+      }),
+    }
+  }
   /// Sets the state of a bit at the coordinates (x, y) in the bit-matrix the
   /// K2Tree represents.
   /// ```
@@ -312,17 +653,12 @@ impl K2Tree {
   /// }
   /// ```
   pub fn set(&mut self, x: usize, y: usize, state: bool) -> Result<()> {
-    let matrix_width = self.matrix_width();
-    if x >= matrix_width || y >= matrix_width {
+    if x >= self.width() || y >= self.height() {
       return Err(Error::Write {
-        source: Box::new(Error::OutOfBounds {
-          x_y: [x, y],
-          min_x_y: [0, 0],
-          max_x_y: [matrix_width-1; 2]
-        })
+        source: Box::new(self.out_of_bounds([x, y]))
       })
     }
-    let stem_len = self.stem_len();
+    let matrix_width = self.matrix_width();
     let leaf_len = self.leaf_len();
     let descend_result = match self.matrix_bit(x, y, matrix_width) {
       Ok(dr) => dr,
@@ -343,7 +679,7 @@ impl K2Tree {
         }
         /* Set the bit in the leaf to the new state */
         let offset = (self.leaf_k * (y - leaf_range.min_y)) + (x - leaf_range.min_x);
-        self.leaves.set(leaf_start+offset, state);
+        self.leaf_ranks.set(&mut self.leaves, leaf_start+offset, state);
         /* If leaf is now all 0's, remove leaf and alter rest of struct to reflect changes.
         Loop up the stems changing the parent bits to 0's and removing stems that become all 0's */
         if !state && all_zeroes(&self.leaves, leaf_start, leaf_start+leaf_len) {
@@ -355,7 +691,7 @@ impl K2Tree {
               - - Alter layer_starts if needed
               - - Find parent bit and set to 0
               - - Repeat until reach stem that isn't all 0's or reach stem layer 0 */
-          if let Err(()) = remove_block(&mut self.leaves, leaf_start, leaf_len) {
+          if let Err(()) = remove_block(&mut self.leaves, 0, leaf_start, leaf_len) {
             return Err(Error::CorruptedK2Tree {
               source: Box::new(Error::Write {
                 source: Box::new(Error::LeafRemovalError {
@@ -365,20 +701,28 @@ impl K2Tree {
               })
             })
           }
+          self.leaf_ranks.changed_from(&self.leaves, leaf_start);
           let stem_bit_pos = self.leaf_parent(leaf_start); //TODO: check
           if self.leaves.is_empty() {
             /* If no more leaves, then remove all stems immediately
             and don't bother with complex stuff below */
-            self.stems = bitvec![0; stem_len];
+            let root_len = self.slayer_len(0);
+            self.stems = bitvec![0; root_len];
+            self.rank_stems();
+            for layer_start in self.slayer_starts[1..].iter_mut() {
+              *layer_start = root_len;
+            }
             return Ok(())
           }
-          self.stems.set(stem_bit_pos, false); //Dead leaf parent bit = 0
+          self.stem_ranks.set(&mut self.stems, stem_bit_pos, false); //Dead leaf parent bit = 0
           let mut curr_layer = self.max_slayers-1;
-          let mut stem_start = self.stem_start(stem_bit_pos);
+          let mut stem_start = self.stem_start(curr_layer, stem_bit_pos);
           while curr_layer > 0
-          && all_zeroes(&self.stems, stem_start, stem_start+stem_len) {
+          && all_zeroes(&self.stems, stem_start, stem_start+self.slayer_len(curr_layer)) {
             let [parent_stem_start, bit_offset] = self.parent(stem_start).unwrap();
-            if let Err(()) = remove_block(&mut self.stems, stem_start, stem_len) {
+            let layer_start = self.layer_start(curr_layer);
+            let stem_len = self.slayer_len(curr_layer);
+            if let Err(()) = remove_block(&mut self.stems, layer_start, stem_start, stem_len) {
               return  Err(Error::CorruptedK2Tree {
                 source: Box::new(Error::Write {
                   source: Box::new(Error::StemRemovalError {
@@ -388,13 +732,15 @@ impl K2Tree {
                 })
               })
             }
-            self.stems.set(parent_stem_start + bit_offset, false);
+            self.stem_ranks.set(&mut self.stems, parent_stem_start + bit_offset, false);
+            self.stem_ranks.changed_from(&self.stems, stem_start);
+            self.stem_removed(curr_layer);
             stem_start = parent_stem_start;
             curr_layer -= 1;
           }
         }
       },
-      DescendResult::Stem(mut stem_start, mut stem_range) if state => {
+      DescendResult::Stem(mut stem_start, mut layer, mut stem_range) if state => {
         /* Descend returning Stem means no Leaf containing bit at (x, y),
         must be located in a submatrix of all 0's.
         If state = false: do nothing 
@@ -409,12 +755,11 @@ impl K2Tree {
           } else {
             self.max_slayers
         };
-        let mut layer = self.layer_from_range(stem_range);
         let mut subranges: SubRanges;
         /* Create correct stems in layers on the way down to the final layer,
         which points to the leaves */
         while layer < self.max_slayers-1 {
-          subranges = match self.to_subranges(stem_range) {
+          subranges = match self.to_subranges(layer, stem_range) {
             Ok(subranges) => subranges,
             Err(error) => return Err(Error::CorruptedK2Tree {
               source: Box::new(Error::Write {
@@ -436,7 +781,7 @@ impl K2Tree {
               })
           };
           /* Change bit containing (x, y) to 1 */
-          self.stems.set(stem_start + child_pos, true);
+          self.stem_ranks.set(&mut self.stems, stem_start + child_pos, true);
           /* If we're not at max possible layer,
           but at the lowest existing layer: Create new layer before
           adding new stem to it.
@@ -459,7 +804,9 @@ impl K2Tree {
           /* We're now working on the child layer */
           layer += 1;
           stem_range = subrange;
-          if let Err(()) = insert_block(&mut self.stems, stem_start, stem_len) {
+          let layer_start = self.layer_start(layer);
+          let stem_len = self.slayer_len(layer);
+          if let Err(()) = insert_block(&mut self.stems, layer_start, stem_start, stem_len) {
             return Err(Error::CorruptedK2Tree {
               source: Box::new(Error::Write {
                 source: Box::new(Error::StemInsertionError {
@@ -469,9 +816,11 @@ impl K2Tree {
               })
             })
           }
+          self.stem_ranks.changed_from(&self.stems, stem_start);
+          self.stem_inserted(layer);
         }
         /* We're at the final stem layer */
-        subranges = match self.to_subranges(stem_range) { 
+        subranges = match self.to_subranges(layer, stem_range) {
           Ok(subranges) => subranges,
           Err(error) => return Err(Error::CorruptedK2Tree {
             source: Box::new(Error::Write {
@@ -493,16 +842,14 @@ impl K2Tree {
             })
         };
         /* Set the correct stem bit to 1 */
-        self.stems.set(stem_start + child_pos, true);
-        /* Find the index to insert the new leaf */
-        let nth_leaf = ones_in_range(
-          &self.stems,
-          self.layer_start(self.max_slayers-1),
-          stem_start + child_pos
-        );
-        let leaf_start = nth_leaf * leaf_len; //TODO: Check
+        self.stem_ranks.set(&mut self.stems, stem_start + child_pos, true);
+        /* Find the index to insert the new leaf, every 1 before it
+        that isn't in the final layer points to a stem instead */
+        let nth_leaf = self.stem_ranks.rank(&self.stems, stem_start + child_pos)
+          - self.stems_after_root();
+        let leaf_start = nth_leaf * leaf_len;
         /* Create new leaf of all 0's */
-        if let Err(()) = insert_block(&mut self.leaves, leaf_start, leaf_len) {
+        if let Err(()) = insert_block(&mut self.leaves, 0, leaf_start, leaf_len) {
           return Err(Error::CorruptedK2Tree {
             source: Box::new(Error::Write {
               source: Box::new(Error::LeafInsertionError {
@@ -514,21 +861,84 @@ impl K2Tree {
         }
         /* Change bit at (x, y) to 1 */
         let leaf_range = subrange;
+        self.leaf_ranks.changed_from(&self.leaves, leaf_start);
         let offset = (self.leaf_k * (y - leaf_range.min_y)) + (x - leaf_range.min_x);
-        self.leaves.set(leaf_start+offset, true);
+        self.leaf_ranks.set(&mut self.leaves, leaf_start+offset, true);
       }
       _ => {},
     };
     Ok(())
   }
-  /// Returns the width of the bit-matrix that a K2Tree represents.
+  /// Sets the state of many bits at once, returning the number of bits whose
+  /// state actually changed.
+  /// 
+  /// Rather than restructuring the K2Tree after every bit, the updates are grouped by
+  /// the blocks they fall in and only those blocks are rebuilt, in a single pass which
+  /// splices them in between the untouched parts of each stem-layer and the leaves.
+  /// If the same bit is set more than once, the last state given for it is used.
   /// 
-  /// The matrix is always square, so this is also the height.
+  /// Unlike `set`, the matrix is grown as much as needed to fit any 1s outside
+  /// of its bounds. 0s outside of its bounds are ignored. A K2Tree with a fixed
+  /// width and height, such as one made with `with_dimensions`, is never grown and
+  /// returns an OutOfBounds error for any 1s outside of them instead. Returns an
+  /// OversizedMatrix error if no matrix_width that fits in a usize can hold a 1.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let mut tree = K2Tree::with_k(2, 2)?;
+  ///   tree.set(0, 0, true)?;
+  ///   let changed = tree.set_many(vec![
+  ///     (0, 0, true),
+  ///     (3, 5, true),
+  ///     (12, 1, true),
+  ///     (7, 7, true),
+  ///     (7, 7, false),
+  ///   ])?;
+  ///   assert_eq!(2, changed);
+  ///   assert_eq!(16, tree.matrix_width());
+  ///   assert_eq!(true, tree.get(12, 1)?);
+  ///   assert_eq!(false, tree.get(7, 7)?);
+  ///   assert_eq!(3, tree.count_ones());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn set_many(&mut self, bits: impl IntoIterator<Item=(usize, usize, bool)>) -> Result<usize> {
+    let bits: Vec<(usize, usize, bool)> = bits.into_iter().collect();
+    if let Some([width, height]) = self.dimensions {
+      let outside = bits.iter().find(|&&(x, y, state)| state && (x >= width || y >= height));
+      if let Some(&(x, y, _)) = outside {
+        return Err(Error::Write {
+          source: Box::new(self.out_of_bounds([x, y]))
+        })
+      }
+    }
+    let max_coord = bits.iter()
+      .filter(|&&(_, _, state)| state)
+      .map(|&(x, y, _)| std::cmp::max(x, y))
+      .max();
+    if let Some(max_coord) = max_coord {
+      let stem_ks = self.stem_ks_to_fit(self.leaf_k, max_coord)?;
+      self.grow_by(stem_ks.len() - self.max_slayers);
+    }
+    /* Put the updates in the same order as the bits in the tree, keeping only
+    the last update to each bit */
+    let matrix_width = self.matrix_width();
+    let mut updates: Vec<(Vec<usize>, bool)> = bits.into_iter()
+      .rev()
+      .filter(|&(x, y, _)| x < matrix_width && y < matrix_width)
+      .map(|(x, y, state)| (self.path_to(x, y), state))
+      .collect();
+    updates.sort_by(|(a, _), (b, _)| a.cmp(b));
+    updates.dedup_by(|(a, _), (b, _)| a == b);
+    Ok(self.merge_updates(&updates))
+  }
+  /// Returns the width of the square bit-matrix that a K2Tree's stems and leaves cover.
   /// 
   /// This can only have certain values, depending on the values of leaf_k and stem_k,
   /// so it is common for a K2Tree's matrix_width to be greater than the matrix it
   /// was built from. Thankfully, trailing rows/columns have no affect on the size
-  /// of the K2Tree.
+  /// of the K2Tree. The size of the matrix the K2Tree represents is given by
+  /// `width` and `height`.
   /// ```
   /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
   ///   use k2_tree::{K2Tree, matrix::BitMatrix};
@@ -539,7 +949,61 @@ impl K2Tree {
   /// }
   /// ```
   pub fn matrix_width(&self) -> usize {
-    self.leaf_k * (self.stem_k.pow(self.max_slayers as u32))
+    match &self.stem_ks {
+      Some(stem_ks) => self.leaf_k * stem_ks.iter().product::<usize>(),
+      None => self.leaf_k * (self.stem_k.pow(self.max_slayers as u32)),
+    }
+  }
+  /// Returns the width of the bit-matrix that a K2Tree represents, which every
+  /// x-coordinate must be less than.
+  /// 
+  /// This is the matrix_width, unless the K2Tree was made with `with_dimensions` or
+  /// from a BitMatrix that is not already matrix_width bits square, in which case
+  /// it is the width that was asked for.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::{K2Tree, matrix::BitMatrix};
+  ///   let tree = K2Tree::from_matrix(BitMatrix::with_dimensions(3, 20), 2, 2)?;
+  ///   assert_eq!(3, tree.width());
+  ///   assert_eq!(20, tree.height());
+  ///   assert_eq!(32, tree.matrix_width());
+  ///   assert_eq!(16, K2Tree::with_k(2, 4)?.width());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn width(&self) -> usize {
+    match self.dimensions {
+      Some([width, _]) => width,
+      None => self.matrix_width(),
+    }
+  }
+  /// Returns the height of the bit-matrix that a K2Tree represents, which every
+  /// y-coordinate must be less than.
+  /// 
+  /// This is the matrix_width unless the K2Tree has a fixed width and height,
+  /// the same as for `width`.
+  pub fn height(&self) -> usize {
+    match self.dimensions {
+      Some([_, height]) => height,
+      None => self.matrix_width(),
+    }
+  }
+  /// Returns the k value of each of the K2Tree's stem-layers, from the root down.
+  /// 
+  /// These are all the stem_k, unless the K2Tree was made with `with_stem_ks`.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   assert_eq!(vec![3, 3], K2Tree::with_k(3, 2)?.stem_ks());
+  ///   assert_eq!(vec![8, 2, 2], K2Tree::with_stem_ks(&[8, 2, 2], 2)?.stem_ks());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn stem_ks(&self) -> Vec<usize> {
+    match &self.stem_ks {
+      Some(stem_ks) => stem_ks.clone(),
+      None => vec![self.stem_k; self.max_slayers],
+    }
   }
   /// Returns an iterator over the K2Tree's stems which produces instances of StemBit.
   /// 
@@ -575,7 +1039,12 @@ impl K2Tree {
   pub fn leaves_raw(&self) -> iterators::LeavesRaw<'_> {
     iterators::LeavesRaw::new(self)
   }
-  /// Increases the height and width of the matrix the K2Tree represents by a factor of k.
+  /// Increases the height and width of the matrix the K2Tree represents by a factor of
+  /// the stem_k, adding a new root stem-layer above the old one.
+  /// 
+  /// A K2Tree with a fixed width and height, such as one made with `with_dimensions`,
+  /// keeps them and only its matrix_width grows. In a K2Tree with a k value for each
+  /// stem-layer, the new root stem-layer has the k value of the old one, the stem_k.
   /// ```
   /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
   ///   use k2_tree::K2Tree;
@@ -591,16 +1060,9 @@ impl K2Tree {
   /// }
   /// ```
   pub fn grow(&mut self) {
-    let stem_len = self.stem_len();
-    self.max_slayers += 1;
-    if !self.is_empty() {
-      /* Only insert the extra layers etc. if the
-      tree isn't all 0s */
-      for _ in 0..stem_len-1 { self.stems.insert(0, false); }
-      self.stems.insert(0, true);
-    }
+    self.grow_by(1);
   }
-  /// Only shrinks the height and width of the matrix the K2Tree represents by a factor of k
+  /// Only shrinks the height and width of the matrix the K2Tree represents by a factor of the stem_k
   /// if it is possible.
   /// 
   /// Does not Err if the matrix cannot be shrunk i.e. it is already at the minimum size.
@@ -622,9 +1084,11 @@ impl K2Tree {
       _ => ()
     }
   }
-  /// Attempts to reduce the height and width of the matrix the K2Tree represents by a factor of k.
+  /// Attempts to reduce the height and width of the matrix the K2Tree represents by a factor of
+  /// the stem_k, removing the root stem-layer so that the one below it becomes the root.
   /// 
-  /// Returns an Err if the matrix cannot be shrunk i.e. it is already at the minimum size.
+  /// Returns an Err if the matrix cannot be shrunk i.e. it is already at the minimum size,
+  /// or its matrix_width would become smaller than a fixed width or height.
   /// ```
   /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
   ///   use k2_tree::K2Tree;
@@ -639,26 +1103,31 @@ impl K2Tree {
   /// ```
   pub fn shrink(&mut self) -> Result<()> {
     let stem_len = self.stem_len();
-    if self.matrix_width() <= self.leaf_k * self.stem_k.pow(2) {
+    if self.max_slayers <= 2 {
       return Err(Error::CouldNotShrink {
         reason: format!("Already at minimum size: {}", self.matrix_width())
       })
     }
+    else if self.dimensions.is_some_and(|[width, height]| {
+      self.matrix_width() / self.stem_k < std::cmp::max(width, height)
+    }) {
+      return Err(Error::CouldNotShrink {
+        reason: format!("Would be smaller than the width and height: {}, {}", self.width(), self.height())
+      })
+    }
     else if self.stems[1..stem_len] != bitbox![0; stem_len-1] {
       return Err(Error::CouldNotShrink {
         reason: "Shrinking would lose information about the matrix".into()
       })
     }
-    self.max_slayers -= 1;
-    /* Remove top layer stem */
-    for _ in 0..stem_len { self.stems.remove(0); }
+    self.shrink_top_layer(stem_len);
     Ok(())
   }
-  /// Reduces the height and width of the matrix the K2Tree represents by a factor of k without
+  /// Reduces the height and width of the matrix the K2Tree represents by a factor of the stem_k without
   /// doing any bounds checking before or integrity checking afterwards.
   /// 
   /// # Safety
-  /// Do not attempt to shrink matrix_width smaller than k^3.
+  /// Do not attempt to shrink the K2Tree to fewer than two stem-layers.
   /// 
   /// ```
   /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
@@ -673,9 +1142,7 @@ impl K2Tree {
   /// ```
   pub unsafe fn shrink_unchecked(&mut self) {
     let stem_len = self.stem_len();
-    self.max_slayers -= 1;
-    /* Remove top layer stem */
-    for _ in 0..stem_len { self.stems.remove(0); }
+    self.shrink_top_layer(stem_len);
   }
   /// Comsumes the K2Tree to produce the bit-matrix it represented.
   /// 
@@ -696,8 +1163,7 @@ impl K2Tree {
   /// }
   /// ```
   pub fn into_matrix(self) -> Result<BitMatrix> {
-    let matrix_width = self.matrix_width();
-    let mut m = BitMatrix::with_dimensions(matrix_width, matrix_width);
+    let mut m = BitMatrix::with_dimensions(self.width(), self.height());
     for (pos, &state) in self.leaves.iter().enumerate() {
       if state {
         let [x, y] = self.get_coords(pos);
@@ -729,8 +1195,7 @@ impl K2Tree {
   /// }
   /// ```
   pub fn to_matrix(&self) -> Result<BitMatrix> {
-    let matrix_width = self.matrix_width();
-    let mut m = BitMatrix::with_dimensions(matrix_width, matrix_width);
+    let mut m = BitMatrix::with_dimensions(self.width(), self.height());
     for (pos, &state) in self.leaves.iter().enumerate() {
       if state {
         let [x, y] = self.get_coords(pos);
@@ -745,7 +1210,8 @@ impl K2Tree {
   }
   /// Constructs a K2Tree which represents the state of the input matrix.
   /// 
-  /// All types that can produce rows of bits are valid inputs.
+  /// The K2Tree has the same width and height as the matrix, so `to_matrix`
+  /// produces the same matrix again.
   /// ```
   /// use k2_tree::{K2Tree, matrix::BitMatrix};
   /// let mut m = BitMatrix::with_dimensions(8, 8);
@@ -753,20 +1219,46 @@ impl K2Tree {
   /// assert!(K2Tree::from_matrix(m, 2, 2).is_ok());
   /// ```
   pub fn from_matrix(matrix: BitMatrix, stem_k: usize, leaf_k: usize) -> Result<Self> {
-    let mut tree = K2Tree::with_k(stem_k, leaf_k)?;
-    while matrix.width > tree.matrix_width()
-    || matrix.height > tree.matrix_width() {
-      tree.grow();
-    }
-    let rows = matrix.into_rows();
-    for (y, row) in rows.into_iter().enumerate() {
-      let xs = one_positions(row.into_iter());
-      for x in xs.into_iter() {
-        tree.set(x, y, true)?;
+    let [width, height] = [matrix.width, matrix.height];
+    let max_slayers = K2Tree::slayers_to_fit(
+      std::cmp::max(width, height).saturating_sub(1),
+      stem_k,
+      leaf_k
+    )?;
+    let mut coords = Vec::new();
+    for (y, row) in matrix.into_rows().into_iter().enumerate() {
+      for x in one_positions(row.into_iter()) {
+        coords.push((x, y));
       }
     }
+    let mut tree = K2Tree::build(coords, vec![stem_k; max_slayers], leaf_k);
+    tree.set_dimensions(width, height);
     Ok(tree)
   }
+  /// Constructs a K2Tree with a 1 at each of the input (x, y) coordinates.
+  /// 
+  /// The K2Tree is built in a single pass after sorting the coordinates,
+  /// rather than by setting each bit in turn. Duplicate coordinates are ignored.
+  /// 
+  /// Returns a SmallKValue error if either k < 2, or an OversizedMatrix error if a
+  /// coordinate is too large for any matrix_width that fits in a usize.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let tree = K2Tree::from_coords(vec![(0, 5), (12, 3), (0, 5)], 2, 2)?;
+  ///   assert_eq!(16, tree.matrix_width());
+  ///   assert_eq!(true, tree.get(0, 5)?);
+  ///   assert_eq!(true, tree.get(12, 3)?);
+  ///   assert_eq!(2, tree.count_ones());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn from_coords(coords: impl IntoIterator<Item=(usize, usize)>, stem_k: usize, leaf_k: usize) -> Result<Self> {
+    let coords: Vec<(usize, usize)> = coords.into_iter().collect();
+    let max_coord = coords.iter().map(|&(x, y)| std::cmp::max(x, y)).max().unwrap_or(0);
+    let max_slayers = K2Tree::slayers_to_fit(max_coord, stem_k, leaf_k)?;
+    Ok(K2Tree::build(coords, vec![stem_k; max_slayers], leaf_k))
+  }
 }
 
 /* Traits */
@@ -780,7 +1272,7 @@ impl core::fmt::Display for K2Tree {
       for bit_pos in layer_starts[layer_num]..layer_starts[layer_num+1] {
         if self.stems[bit_pos] { s.push('1'); }
         else { s.push('0'); }
-        if i == self.stem_k*self.stem_k
+        if i == self.slayer_len(layer_num)
         && (bit_pos - layer_starts[layer_num]) < self.layer_len(layer_num)-1 {
           s.push_str(", ");
           i = 1;
@@ -804,11 +1296,26 @@ impl core::fmt::Display for K2Tree {
     write!(f, "[{}]", s)
   }
 }
+impl std::iter::FromIterator<(usize, usize)> for K2Tree {
+  /// Constructs a K2Tree with k = 2 and a 1 at each of the (x, y) coordinates.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a coordinate is too large for a matrix_width with k values of 2 to
+  /// fit in a usize, such as `(usize::MAX, 0)`. Use `from_coords` to get an
+  /// OversizedMatrix error instead.
+  fn from_iter<I: IntoIterator<Item=(usize, usize)>>(iter: I) -> Self {
+    K2Tree::from_coords(iter, 2, 2).unwrap()
+  }
+}
 impl PartialEq for K2Tree {
   fn eq(&self, other: &Self) -> bool {
     self.stem_k == other.stem_k
     && self.leaf_k == other.leaf_k
     && self.max_slayers == other.max_slayers
+    && self.width() == other.width()
+    && self.height() == other.height()
+    && self.stem_ks == other.stem_ks
     && self.stems == other.stems
     && self.leaves == other.leaves
   }
@@ -824,18 +1331,33 @@ impl std::hash::Hash for K2Tree {
     self.stem_k.hash(state);
     self.leaf_k.hash(state);
     self.max_slayers.hash(state);
+    self.width().hash(state);
+    self.height().hash(state);
+    self.stem_ks.hash(state);
     self.stems.hash(state);
     self.leaves.hash(state);
   }
 }
 impl Serialize for K2Tree {
   fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
-    let mut state = serializer.serialize_struct("K2Tree", 5)?;
+    /* The width and height are only included if they are not the matrix_width,
+    and the k value of each stem-layer if they are not all the stem_k */
+    let len = 5
+      + if self.dimensions.is_some() { 2 } else { 0 }
+      + if self.stem_ks.is_some() { 1 } else { 0 };
+    let mut state = serializer.serialize_struct("K2Tree", len)?;
     state.serialize_field("stemK", &self.stem_k)?;
     state.serialize_field("leafK", &self.leaf_k)?;
     state.serialize_field("maxStemLayers", &self.max_slayers)?;
     state.serialize_field("stems", &self.stems.clone().into_vec() as &Vec<usize>)?;
     state.serialize_field("leaves", &self.leaves.clone().into_vec() as &Vec<usize>)?;
+    if let Some([width, height]) = self.dimensions {
+      state.serialize_field("width", &width)?;
+      state.serialize_field("height", &height)?;
+    }
+    if let Some(stem_ks) = &self.stem_ks {
+      state.serialize_field("stemKs", stem_ks)?;
+    }
     state.end()
   }
 }
@@ -846,7 +1368,10 @@ impl<'de> Deserialize<'de> for K2Tree {
       LeafK,
       MaxStemLayers,
       Stems,
-      Leaves
+      Leaves,
+      Width,
+      Height,
+      StemKs,
     }
     impl<'de> Deserialize<'de> for Field {
       fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Field, D::Error> {
@@ -863,6 +1388,9 @@ impl<'de> Deserialize<'de> for K2Tree {
                 "maxStemLayers" => Ok(Field::MaxStemLayers),
                 "stems" => Ok(Field::Stems),
                 "leaves" => Ok(Field::Leaves),
+                "width" => Ok(Field::Width),
+                "height" => Ok(Field::Height),
+                "stemKs" => Ok(Field::StemKs),
                 _ => Err(de::Error::unknown_field(value, FIELDS)),
               }
             }
@@ -882,6 +1410,9 @@ impl<'de> Deserialize<'de> for K2Tree {
         let mut max_slayers = None;
         let mut stems = None;
         let mut leaves = None;
+        let mut width = None;
+        let mut height = None;
+        let mut stem_ks = None;
         while let Some(key) = map.next_key()? {
           match key {
             Field::StemK => {
@@ -913,6 +1444,24 @@ impl<'de> Deserialize<'de> for K2Tree {
                 return Err(de::Error::duplicate_field("leaves"));
               }
               leaves = Some(BitVec::from_vec(map.next_value::<Vec<usize>>()?));
+            },
+            Field::Width => {
+              if width.is_some() {
+                return Err(de::Error::duplicate_field("width"));
+              }
+              width = Some(map.next_value()?);
+            },
+            Field::Height => {
+              if height.is_some() {
+                return Err(de::Error::duplicate_field("height"));
+              }
+              height = Some(map.next_value()?);
+            },
+            Field::StemKs => {
+              if stem_ks.is_some() {
+                return Err(de::Error::duplicate_field("stemKs"));
+              }
+              stem_ks = Some(map.next_value::<Vec<usize>>()?);
             }
           }
         }
@@ -925,32 +1474,68 @@ impl<'de> Deserialize<'de> for K2Tree {
         // If any values missing, would have thrown by now
         // Remove any trailing zeroes that comes from converting blindly
         // from Vec<usize> to list of bits
-        let stem_len: usize = stem_k.pow(2);
-        let leaf_len: usize = leaf_k.pow(2);
-        let last_1_stem = one_positions_bv(&stems).pop().unwrap();
-        let new_stem_len = if last_1_stem+1 % stem_len == 0 {
-            last_1_stem
-          }
-          else {
-            ((last_1_stem / stem_len) + 1) * stem_len
+        /* The k values are needed to trim the stems and leaves, so are checked first */
+        if stem_k < 2 {
+          return Err(de::Error::custom(Error::from(StructureError::SmallStemKValue { stem_k })))
+        }
+        if leaf_k < 2 {
+          return Err(de::Error::custom(Error::from(StructureError::SmallLeafKValue { leaf_k })))
+        }
+        let matrix_width = match &stem_ks {
+          Some(stem_ks) => validate::checked_matrix_width(stem_ks.iter().copied(), leaf_k),
+          None => validate::checked_matrix_width(std::iter::repeat(stem_k).take(max_slayers), leaf_k),
         };
-        stems.resize(new_stem_len, false);
-        let last_1_leaf = one_positions_bv(&leaves).pop().unwrap();
-        let new_leaf_len = if last_1_leaf+1 % leaf_len == 0 {
-            last_1_leaf
-          }
-          else {
-            ((last_1_leaf / leaf_len) + 1) * leaf_len
+        if matrix_width.is_none() {
+          return Err(de::Error::invalid_value(
+            de::Unexpected::Unsigned(max_slayers as u64),
+            &"a number of stem-layers whose matrix_width fits in a usize"
+          ))
+        }
+        let stem_ks = match stem_ks {
+          Some(stem_ks) if stem_ks.len() != max_slayers || stem_ks.first() != Some(&stem_k) => {
+            return Err(de::Error::custom(Error::from(
+              StructureError::StemKsMismatch { stem_k, max_slayers, stem_ks }
+            )))
+          },
+          Some(stem_ks) => stem_ks,
+          None => vec![stem_k; max_slayers],
+        };
+        let (stem_len, leaf_len) = match (validate::block_len(stem_k), validate::block_len(leaf_k)) {
+          (Ok(stem_len), Ok(leaf_len)) => (stem_len, leaf_len),
+          (Err(error), _) | (_, Err(error)) => return Err(de::Error::custom(Error::from(error))),
+        };
+        if stem_ks.iter().all(|&k| k == stem_k) {
+          let new_stem_len = match one_positions_bv(&stems).pop() {
+            Some(last_1_stem) => ((last_1_stem / stem_len) + 1) * stem_len,
+            None => stem_len,
+          };
+          stems.resize(new_stem_len, false);
+        }
+        else if let Some(len) = validate::stems_len(&stem_ks, &stems) {
+          /* The stems of each layer are different lengths, so the layers are walked to find
+          where they end. Only 0s after that end can have come from the conversion. */
+          if stems[len..].not_any() { stems.truncate(len); }
+        }
+        let new_leaf_len = match one_positions_bv(&leaves).pop() {
+          Some(last_1_leaf) => ((last_1_leaf / leaf_len) + 1) * leaf_len,
+          None => 0,
         };
         leaves.resize(new_leaf_len, false);
-
-        Ok(K2Tree {
-          stem_k,
-          leaf_k,
-          max_slayers,
-          stems,
-          leaves
-        })
+        if let Err(error) = validate::check_structure(&stem_ks, leaf_k, &stems, &leaves) {
+          return Err(de::Error::custom(Error::from(error)))
+        }
+        let mut tree = K2Tree::from_bits_with_stem_ks(stem_ks, leaf_k, stems, leaves);
+        /* The width and height are either both present or both absent */
+        match (width, height) {
+          (Some(width), Some(height)) => tree.set_dimensions(width, height),
+          (Some(_), None) => return Err(de::Error::missing_field("height")),
+          (None, Some(_)) => return Err(de::Error::missing_field("width")),
+          (None, None) => (),
+        }
+        if let Err(error) = tree.check_dimensions() {
+          return Err(de::Error::custom(Error::from(error)))
+        }
+        Ok(tree)
       }
     }
     const FIELDS: &[&str] = &[
@@ -958,16 +1543,228 @@ impl<'de> Deserialize<'de> for K2Tree {
       "leaf_k",
       "max_slayers",
       "stems",
-      "leaves"
+      "leaves",
+      "width",
+      "height",
+      "stemKs",
     ];
     deserializer.deserialize_struct("K2Tree", FIELDS, K2TreeVisitor)
   }
 }
 
 /* Private */
+impl K2Tree {
+  fn build(coords: Vec<(usize, usize)>, stem_ks: Vec<usize>, leaf_k: usize) -> Self {
+    let mut builder = K2TreeBuilder::with_stem_ks(stem_ks, leaf_k);
+    let mut keys: Vec<u128> = coords.into_iter()
+      .map(|(x, y)| builder.z_order(x, y))
+      .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    for key in keys.into_iter() {
+      builder.push_key(key);
+    }
+    builder.finish()
+  }
+  /* The position of the child containing (x, y) in each stem from the root down,
+  followed by the offset of (x, y) in its leaf */
+  fn path_to(&self, x: usize, y: usize) -> Vec<usize> {
+    let mut path = Vec::with_capacity(self.max_slayers+1);
+    let mut sub_width = self.matrix_width();
+    for layer in 0..self.max_slayers {
+      let stem_k = self.slayer_k(layer);
+      sub_width /= stem_k;
+      path.push(((y / sub_width) % stem_k) * stem_k + (x / sub_width) % stem_k);
+    }
+    path.push((y % self.leaf_k) * self.leaf_k + x % self.leaf_k);
+    path
+  }
+  /* Applies updates sorted by their paths, with no two to the same bit, returning
+  the number of bits that changed. Only the blocks on the paths of the updates are
+  visited and rebuilt. */
+  fn merge_updates(&mut self, updates: &[(Vec<usize>, bool)]) -> usize {
+    let max_slayers = self.max_slayers;
+    /* Find the touched blocks from the root down. The updates beneath each child
+    of a block are a run of those beneath the block, and blocks in the same layer
+    are in the same order as their paths. */
+    let root = TouchedBlock { updates: 0..updates.len(), pos: 0, exists: true, bits: BitVec::new() };
+    let mut layers = vec![vec![root]];
+    for layer in 0..max_slayers {
+      let mut children = Vec::new();
+      for block in layers[layer].iter() {
+        let mut first = block.updates.start;
+        while first < block.updates.end {
+          let child = updates[first].0[layer];
+          let end = first + updates[first..block.updates.end]
+            .partition_point(|(path, _)| path[layer] == child);
+          /* A child of a block that does not exist yet goes where the block's
+          first child would */
+          let bit_pos = if block.exists { block.pos + child } else { block.pos };
+          let pos = if layer == max_slayers-1 {
+            (self.stem_ranks.rank(&self.stems, bit_pos) - self.stems_after_root()) * self.leaf_len()
+          }
+          else {
+            self.child_start(layer, bit_pos)
+          };
+          children.push(TouchedBlock {
+            updates: first..end,
+            pos,
+            exists: block.exists && self.stems[bit_pos],
+            bits: BitVec::new(),
+          });
+          first = end;
+        }
+      }
+      layers.push(children);
+    }
+    /* Apply the updates to the leaves, then work out the bits of each touched
+    stem from the bottom up */
+    let leaf_len = self.leaf_len();
+    let mut changed = 0;
+    for leaf in layers[max_slayers].iter_mut() {
+      leaf.bits = if leaf.exists { self.leaves[leaf.pos..leaf.pos+leaf_len].to_owned() }
+        else { bitvec![0; leaf_len] };
+      for (path, state) in updates[leaf.updates.clone()].iter() {
+        if leaf.bits[path[max_slayers]] != *state {
+          leaf.bits.set(path[max_slayers], *state);
+          changed += 1;
+        }
+      }
+    }
+    if changed == 0 { return 0 }
+    for layer in (0..max_slayers).rev() {
+      let stem_len = self.slayer_len(layer);
+      let (above, below) = layers.split_at_mut(layer+1);
+      let mut children = below[0].iter().peekable();
+      for block in above[layer].iter_mut() {
+        block.bits = if block.exists { self.stems[block.pos..block.pos+stem_len].to_owned() }
+          else { bitvec![0; stem_len] };
+        while let Some(child) = children.peek() {
+          if child.updates.start >= block.updates.end { break }
+          block.bits.set(updates[child.updates.start].0[layer], child.bits.any());
+          children.next();
+        }
+      }
+    }
+    /* Splice the touched blocks in between the untouched runs of each layer,
+    leaving out any blocks that are now all 0s except for the root */
+    let mut stems = BitVec::with_capacity(self.stems.len());
+    let mut slayer_starts = Vec::with_capacity(self.slayer_starts.len());
+    let mut first_stem_change = None;
+    for (layer, blocks) in layers[..max_slayers].iter().enumerate() {
+      let layer_end = if layer == max_slayers-1 { self.stems.len() }
+        else { self.layer_start(layer+1) };
+      slayer_starts.push(stems.len());
+      let change = splice_blocks(&mut stems, &self.stems[..layer_end], self.layer_start(layer), blocks, self.slayer_len(layer), layer == 0);
+      first_stem_change = first_stem_change.or(change);
+    }
+    /* Any layer starts past the last stem-layer move as far as its end did */
+    for &layer_start in self.slayer_starts[max_slayers..].iter() {
+      slayer_starts.push(layer_start + stems.len() - self.stems.len());
+    }
+    let mut leaves = BitVec::with_capacity(self.leaves.len());
+    let first_leaf_change = splice_blocks(&mut leaves, &self.leaves, 0, &layers[max_slayers], leaf_len, false);
+    self.stems = stems;
+    self.leaves = leaves;
+    self.slayer_starts = slayer_starts;
+    /* Everything before the first change is where it was, so only the
+    rank directories from there onwards need recounting */
+    if let Some(pos) = first_stem_change {
+      self.stem_ranks.changed_from(&self.stems, pos);
+    }
+    if let Some(pos) = first_leaf_change {
+      self.leaf_ranks.changed_from(&self.leaves, pos);
+    }
+    changed
+  }
+  /* Adds `layers` new root stem-layers at once, each with the k value of the
+  old root, rebuilding the stems and their index only once */
+  fn grow_by(&mut self, layers: usize) {
+    if layers == 0 { return }
+    let stem_len = self.stem_len();
+    self.max_slayers += layers;
+    if let Some(stem_ks) = &mut self.stem_ks {
+      stem_ks.splice(0..0, std::iter::repeat(self.stem_k).take(layers));
+    }
+    if !self.is_empty() {
+      /* Only insert the extra layers etc. if the
+      tree isn't all 0s. Each new stem only has the old root beneath it,
+      as its first child */
+      let mut stems = BitVec::with_capacity(layers*stem_len + self.stems.len());
+      for _ in 0..layers {
+        stems.push(true);
+        stems.extend(std::iter::repeat(false).take(stem_len-1));
+      }
+      stems.extend_from_slice(&self.stems);
+      self.stems = stems;
+      self.rank_stems();
+      for layer_start in self.slayer_starts.iter_mut() {
+        *layer_start += layers*stem_len;
+      }
+      self.slayer_starts.splice(0..0, (0..layers).map(|layer| layer*stem_len));
+    }
+    else {
+      self.slayer_starts.extend(std::iter::repeat(stem_len).take(layers));
+    }
+  }
+  fn shrink_top_layer(&mut self, stem_len: usize) {
+    self.max_slayers -= 1;
+    if let Some(mut stem_ks) = self.stem_ks.take() {
+      /* The stem-layer below the root becomes the root, with its own k value */
+      stem_ks.remove(0);
+      self.store_stem_ks(stem_ks);
+    }
+    if self.is_empty() {
+      /* An empty tree only ever has the one stem, keep it */
+      let root_len = self.stem_len();
+      self.stems.resize(root_len, false);
+      self.reindex();
+      return
+    }
+    /* Remove top layer stem */
+    for _ in 0..stem_len { self.stems.remove(0); }
+    self.rank_stems();
+    self.slayer_starts.remove(0);
+    for layer_start in self.slayer_starts.iter_mut() {
+      *layer_start -= stem_len;
+    }
+  }
+}
+/* A block on the path to at least one of the updates given to set_many */
+struct TouchedBlock {
+  /* The run of updates beneath the block */
+  updates: std::ops::Range<usize>,
+  /* The start of the block in the stems or leaves, or where it is to be inserted */
+  pos: usize,
+  exists: bool,
+  /* The bits of the block once the updates have been applied */
+  bits: BitVec,
+}
+/* Copies `old[start..]` onto the end of `bits`, with the touched blocks in place of the
+old ones or inserted where they do not exist yet. Blocks of all 0s are left out unless
+`keep_empty`. Returns the position in `bits` of the first block that differs from
+what was there before, if any. */
+fn splice_blocks(bits: &mut BitVec, old: &BitSlice, start: usize, blocks: &[TouchedBlock], block_len: usize, keep_empty: bool) -> Option<usize> {
+  let mut copied = start;
+  let mut first_change = None;
+  for block in blocks.iter() {
+    bits.extend_from_slice(&old[copied..block.pos]);
+    let keep = keep_empty || block.bits.any();
+    let changed = match (block.exists, keep) {
+      (true, true) => old[block.pos..block.pos+block_len] != block.bits[..],
+      (false, false) => false,
+      _ => true,
+    };
+    if changed && first_change.is_none() { first_change = Some(bits.len()); }
+    if keep { bits.extend_from_slice(&block.bits); }
+    copied = if block.exists { block.pos + block_len } else { block.pos };
+  }
+  bits.extend_from_slice(&old[copied..]);
+  first_change
+}
 enum DescendResult {
   Leaf(usize, Range2D), //leaf_start, leaf_range
-  Stem(usize, Range2D), //stem_start, stem_range
+  Stem(usize, usize, Range2D), //stem_start, layer, stem_range
 }
 struct DescendEnv {
   /* Allows for descend to be recursive without parameter hell */
@@ -976,12 +1773,111 @@ struct DescendEnv {
   slayer_max: usize,
 }
 impl K2Tree {
-  fn layer_from_range(&self, r: Range2D) -> usize {
-    (self.max_slayers+1) -
-    (
-      ((r.width()/self.leaf_k) as f64).log(self.stem_k as f64) as usize
-      +1
-    )
+  fn check_range(&self, x: &RangeInclusive<usize>, y: &RangeInclusive<usize>) -> Result<()> {
+    let x_out = !x.is_empty() && *x.end() >= self.width();
+    let y_out = !y.is_empty() && *y.end() >= self.height();
+    if x_out || y_out {
+      /* The corner of the range that lies past only the axes which are out of bounds */
+      let x_y = [
+        if x_out { *x.end() } else { *x.start() },
+        if y_out { *y.end() } else { *y.start() },
+      ];
+      return Err(Error::Read {
+        source: Box::new(self.out_of_bounds(x_y))
+      })
+    }
+    Ok(())
+  }
+  /* An OutOfBounds error for coordinates outside of the width and height */
+  fn out_of_bounds(&self, x_y: [usize; 2]) -> Error {
+    Error::OutOfBounds {
+      x_y,
+      min_x_y: [0, 0],
+      max_x_y: [self.width().saturating_sub(1), self.height().saturating_sub(1)]
+    }
+  }
+  /* Sets the width and height of the matrix, which are only stored if they are
+  not both the matrix_width so that the matrix_width can change freely otherwise */
+  pub(crate) fn set_dimensions(&mut self, width: usize, height: usize) {
+    let matrix_width = self.matrix_width();
+    self.dimensions = if width == matrix_width && height == matrix_width { None }
+      else { Some([width, height]) };
+  }
+  /* Builds a K2Tree from its raw parts like `from_bits`, with a k value for each stem-layer */
+  pub(crate) fn from_bits_with_stem_ks(stem_ks: Vec<usize>, leaf_k: usize, stems: BitVec, leaves: BitVec) -> Self {
+    let mut tree = K2Tree {
+      stem_k: stem_ks[0],
+      leaf_k,
+      max_slayers: stem_ks.len(),
+      stems,
+      leaves,
+      stem_ranks: RankSelect::default(),
+      leaf_ranks: RankSelect::default(),
+      slayer_starts: Vec::new(),
+      dimensions: None,
+      stem_ks: None,
+    };
+    tree.store_stem_ks(stem_ks);
+    tree.reindex();
+    tree
+  }
+  /* Sets the k value of each stem-layer, which are only stored if they are not all
+  the same so that every other K2Tree takes the simpler paths */
+  fn store_stem_ks(&mut self, stem_ks: Vec<usize>) {
+    self.stem_k = stem_ks[0];
+    self.max_slayers = stem_ks.len();
+    self.stem_ks = if stem_ks.iter().all(|&stem_k| stem_k == self.stem_k) { None }
+      else { Some(stem_ks) };
+  }
+  fn any_in(&self, layer: usize, stem_start: usize, range: Range2D, query: &Range2D) -> Result<bool> {
+    let subranges = self.to_subranges(layer, range)?;
+    for child_pos in 0..self.slayer_len(layer) {
+      let subrange = subranges[child_pos];
+      if !self.stems[stem_start+child_pos] || !query.intersects(&subrange) { continue }
+      /* A 1 in the stems means there is a 1 somewhere in its subrange */
+      if query.contains_range(&subrange) { return Ok(true) }
+      if layer == self.max_slayers-1 {
+        let leaf_start = self.stem_to_leaf_start(stem_start+child_pos)
+          .map_err(|_| Error::TraverseError{x: subrange.min_x, y: subrange.min_y})?;
+        for offset in 0..self.leaf_len() {
+          let x = subrange.min_x + (offset % self.leaf_k);
+          let y = subrange.min_y + (offset / self.leaf_k);
+          if self.leaves[leaf_start+offset] && query.contains(x, y) { return Ok(true) }
+        }
+      }
+      else {
+        let child_stem = self.child_stem(layer, stem_start, child_pos)
+          .map_err(|_| Error::TraverseError{x: subrange.min_x, y: subrange.min_y})?;
+        if self.any_in(layer+1, child_stem, subrange, query)? { return Ok(true) }
+      }
+    }
+    Ok(false)
+  }
+  fn count_in(&self, layer: usize, stem_start: usize, range: Range2D, query: &Range2D) -> Result<usize> {
+    let subranges = self.to_subranges(layer, range)?;
+    let mut count = 0;
+    for child_pos in 0..self.slayer_len(layer) {
+      let subrange = subranges[child_pos];
+      if !self.stems[stem_start+child_pos] || !query.intersects(&subrange) { continue }
+      if query.contains_range(&subrange) {
+        count += self.subtree_ones(layer, stem_start+child_pos);
+      }
+      else if layer == self.max_slayers-1 {
+        let leaf_start = self.stem_to_leaf_start(stem_start+child_pos)
+          .map_err(|_| Error::TraverseError{x: subrange.min_x, y: subrange.min_y})?;
+        for offset in 0..self.leaf_len() {
+          let x = subrange.min_x + (offset % self.leaf_k);
+          let y = subrange.min_y + (offset / self.leaf_k);
+          if self.leaves[leaf_start+offset] && query.contains(x, y) { count += 1; }
+        }
+      }
+      else {
+        let child_stem = self.child_stem(layer, stem_start, child_pos)
+          .map_err(|_| Error::TraverseError{x: subrange.min_x, y: subrange.min_y})?;
+        count += self.count_in(layer+1, child_stem, subrange, query)?;
+      }
+    }
+    Ok(count)
   }
   fn matrix_bit(&self, x: usize, y: usize, m_width: usize) -> Result<DescendResult> {
     let env = DescendEnv {
@@ -992,10 +1888,10 @@ impl K2Tree {
     self.descend(&env, 0, 0, Range2D::new(0, m_width-1, 0, m_width-1))
   }
   fn descend(&self, env: &DescendEnv, layer: usize, stem_pos: usize, range: Range2D) -> Result<DescendResult> {
-    let subranges = self.to_subranges(range)?;
-    for (child_pos, child) in self.stems[stem_pos..stem_pos+self.stem_len()].iter().enumerate() {
+    let subranges = self.to_subranges(layer, range)?;
+    for (child_pos, child) in self.stems[stem_pos..stem_pos+self.slayer_len(layer)].iter().enumerate() {
       if subranges[child_pos].contains(env.x, env.y) {
-        if !child { return Ok(DescendResult::Stem(stem_pos, range)) } //The bit exists within a range that has all zeros
+        if !child { return Ok(DescendResult::Stem(stem_pos, layer, range)) } //The bit exists within a range that has all zeros
         else if layer == env.slayer_max {
           let leaf_start = match self.stem_to_leaf_start(stem_pos + child_pos) {
             Ok(ls) => ls,
@@ -1023,29 +1919,6 @@ impl K2Tree {
     }
     unreachable!()
   }
-  fn num_stems_before_child(&self, bit_pos: usize, layer: usize) -> usize {
-    ones_in_range(&self.stems, self.layer_start(layer), bit_pos)
-  }
-  fn stem_to_leaf_start(&self, stem_bitpos: usize) -> std::result::Result<usize, ()> {
-    if !self.stems[stem_bitpos] { Err(()) }
-    else {
-      let nth_leaf = ones_in_range(
-        &self.stems,
-        self.layer_start(self.max_slayers-1),
-        stem_bitpos
-      );
-      Ok(nth_leaf * self.leaf_len())
-    }
-  }
-  fn child_stem(&self, layer: usize, stem_start: usize, nth_child: usize) -> std::result::Result<usize, ()> {
-    if !self.stems[stem_start+nth_child]
-    || layer == self.max_slayers-1 {
-      /* If stem_bit is 0 or final stem layer, cannot have children */
-      return Err(())
-    }
-    Ok(self.layer_start(layer+1)
-    + (self.num_stems_before_child(stem_start+nth_child, layer) * self.stem_len()))
-  }
 }
 
 /* Private funcs used in testing */
@@ -1078,13 +1951,13 @@ mod api {
   use bitvec::bitbox;
   #[test]
   fn new() {
-    let expected = K2Tree {
-      stem_k: 2,
-      leaf_k: 2,
-      max_slayers: 2,
-      stems: bitvec![0,0,0,0],
-      leaves: bitvec![],
-    };
+    let expected = K2Tree::from_bits(
+      2,
+      2,
+      2,
+      bitvec![0,0,0,0],
+      bitvec![]
+    );
     assert_eq!(K2Tree::new(), expected);
   }
   #[test]
@@ -1096,13 +1969,13 @@ mod api {
   fn with_k_1() -> Result<()> {
     for stem_k in 2..9usize {
       for leaf_k in 2..9usize {
-        let expected = K2Tree {
+        let expected = K2Tree::from_bits(
           stem_k,
           leaf_k,
-          max_slayers: 2,
-          stems: bitvec![0; stem_k.pow(2)],
-          leaves: BitVec::new(),
-        };
+          2,
+          bitvec![0; stem_k.pow(2)],
+          BitVec::new()
+        );
         assert_eq!(K2Tree::with_k(stem_k, leaf_k)?, expected);
       }
     }
@@ -1121,6 +1994,24 @@ mod api {
     Ok(())
   }
   #[test]
+  fn with_dimensions() -> Result<()> {
+    let mut tree = K2Tree::with_dimensions(2, 2, 3, 20)?;
+    assert_eq!([3, 20, 32], [tree.width(), tree.height(), tree.matrix_width()]);
+    tree.set(2, 19, true)?;
+    assert!(tree.get(2, 19)?);
+    assert!(tree.get(3, 0).is_err());
+    assert!(tree.set(0, 20, true).is_err());
+    assert_eq!(vec![19], tree.predecessors(2)?);
+    assert_eq!(3, tree.get_row(19)?.len());
+    assert_eq!(20, tree.get_column(2)?.len());
+    /* 1s outside the width and height are rejected, not grown into */
+    assert!(tree.set_many(vec![(0, 0, true), (5, 5, true)]).is_err());
+    assert_eq!(1, tree.count_ones());
+    /* Shrinking can't go below the width and height */
+    assert!(tree.shrink().is_err());
+    Ok(())
+  }
+  #[test]
   fn set_stem_k_0() {
     let mut tree = K2Tree::new();
     for valid_k in 2..7 {
@@ -1134,39 +2025,39 @@ mod api {
   fn set_stem_k_1() {
     let mut tree = K2Tree::test_tree(2);
     assert!(tree.set_stem_k(3).is_ok());
-    let expected = K2Tree {
-      stem_k: 3,
-      leaf_k: 2,
-      max_slayers: 2,
-      stems: bitvec![
+    let expected = K2Tree::from_bits(
+      3,
+      2,
+      2,
+      bitvec![
         1,1,0,0,0,0,0,0,0, 0,0,1,0,0,0,1,0,1,
         1,0,0,1,0,0,0,0,0
       ],
-      leaves: bitvec![
+      bitvec![
         0,1,1,0, 1,0,0,0, 0,1,1,0,
         0,1,0,1, 1,1,0,0
-      ],
-    };
+      ]
+    );
     assert_eq!(tree, expected);
   }
   #[test]
   fn set_stem_k_2() {
     let mut tree = K2Tree::test_tree(3);
     assert!(tree.set_stem_k(2).is_ok());
-    let expected = K2Tree {
-      stem_k: 2,
-      leaf_k: 3,
-      max_slayers: 4,
-      stems: bitvec![
+    let expected = K2Tree::from_bits(
+      2,
+      3,
+      4,
+      bitvec![
         1,0,0,0, 1,1,1,0, 0,1,1,1, 1,0,0,0, 0,0,1,1, //final layer begins here
         0,1,0,0, 0,0,1,0, 0,0,0,1, 1,0,0,0, 1,0,0,0, 0,1,0,0
       ],
-      leaves: bitvec![
+      bitvec![
         0,1,0,1,0,0,0,0,1, 1,0,0,0,0,0,0,0,0,
         0,1,0,1,0,0,0,0,0, 1,0,0,1,0,0,1,0,0,
         1,0,0,0,0,0,0,0,0, 0,1,0,1,0,0,0,0,0
-      ],
-    };
+      ]
+    );
     assert_eq!(tree, expected);
   }
   #[test]
@@ -1183,39 +2074,39 @@ mod api {
   fn set_leaf_k_1() {
     let mut tree = K2Tree::test_tree(2);
     assert!(tree.set_leaf_k(3).is_ok());
-    let expected = K2Tree {
-      stem_k: 2,
-      leaf_k: 3,
-      max_slayers: 2,
-      stems: bitvec![
+    let expected = K2Tree::from_bits(
+      2,
+      3,
+      2,
+      bitvec![
         1,1,0,0, 0,1,1,1, 1,0,0,0,
       ],
-      leaves: bitvec![
+      bitvec![
         0,0,1,0,1,0,0,0,0, 0,0,0,1,0,0,0,0,0,
         0,0,0,0,0,1,0,1,0, 0,1,0,0,1,0,1,1,0
-      ],
-    };
+      ]
+    );
     assert_eq!(tree, expected);
   }
   #[test]
   fn set_leaf_k_2() {
     let mut tree = K2Tree::test_tree(3);
     assert!(tree.set_leaf_k(2).is_ok());
-    let expected = K2Tree {
-      stem_k: 3,
-      leaf_k: 2,
-      max_slayers: 3,
-      stems: bitvec![
+    let expected = K2Tree::from_bits(
+      3,
+      2,
+      3,
+      bitvec![
         1,0,0,1,0,0,0,0,0, 0,1,1,1,1,0,0,0,0, 1,1,0,0,0,0,0,0,0, //final layer starts below
         0,1,1,0,0,1,0,0,0, 1,0,0,1,0,0,0,0,0, 0,0,0,1,0,0,0,0,0,
         0,0,0,0,0,1,0,1,0, 1,0,0,0,0,0,0,0,0, 0,1,1,0,0,0,0,0,0
       ],
-      leaves: bitvec![
+      bitvec![
         0,0,0,1, 1,0,0,0, 0,1,0,0, 1,0,1,0, 1,0,0,0,
         0,0,1,0, 0,0,1,0, 0,1,0,0, 1,0,0,0, 0,0,0,1,
         1,0,0,0
-      ],
-    };
+      ]
+    );
     assert_eq!(tree, expected);
   }
   #[test]
@@ -1291,6 +2182,123 @@ mod api {
     Ok(())
   }
   #[test]
+  fn successors() -> Result<()> {
+    for k in 2..=3 {
+      let tree = K2Tree::test_tree(k);
+      for y in 0..tree.matrix_width() {
+        let expected = one_positions(tree.get_row(y)?.into_iter());
+        assert_eq!(expected, tree.successors(y)?);
+      }
+    }
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let tree = K2Tree::from_matrix(K2Tree::test_matrix(3), stem_k, leaf_k)?;
+        for y in 0..tree.height() {
+          let expected = one_positions(tree.get_row(y)?.into_iter());
+          assert_eq!(expected, tree.successors(y)?);
+        }
+      }
+    }
+    let tree = K2Tree::test_tree(2);
+    assert!(tree.successors(8).is_err());
+    assert!(K2Tree::new().successors(0)?.is_empty());
+    Ok(())
+  }
+  #[test]
+  fn predecessors() -> Result<()> {
+    for k in 2..=3 {
+      let tree = K2Tree::test_tree(k);
+      for x in 0..tree.matrix_width() {
+        let expected = one_positions(tree.get_column(x)?.into_iter());
+        assert_eq!(expected, tree.predecessors(x)?);
+      }
+    }
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let tree = K2Tree::from_matrix(K2Tree::test_matrix(3), stem_k, leaf_k)?;
+        for x in 0..tree.width() {
+          let expected = one_positions(tree.get_column(x)?.into_iter());
+          assert_eq!(expected, tree.predecessors(x)?);
+        }
+      }
+    }
+    let tree = K2Tree::test_tree(2);
+    assert!(tree.predecessors(8).is_err());
+    assert!(K2Tree::new().predecessors(0)?.is_empty());
+    Ok(())
+  }
+  #[test]
+  fn ones_in_range() -> Result<()> {
+    let ranges = [
+      [0, 26, 0, 26], [0, 7, 0, 7], [4, 7, 0, 3], [3, 12, 1, 9],
+      [9, 9, 0, 26], [0, 26, 18, 18], [10, 17, 20, 26], [5, 4, 0, 26],
+    ];
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let matrix = K2Tree::test_matrix(3);
+        let tree = K2Tree::from_matrix(matrix.clone(), stem_k, leaf_k)?;
+        for &[min_x, max_x, min_y, max_y] in ranges.iter() {
+          let expected: Vec<(usize, usize)> = tree.leaves()
+            .filter(|bit| bit.value
+              && bit.x >= min_x && bit.x <= max_x
+              && bit.y >= min_y && bit.y <= max_y)
+            .map(|bit| (bit.x, bit.y))
+            .collect();
+          let actual = tree.ones_in_range(min_x..=max_x, min_y..=max_y)?;
+          let mut sorted_expected = expected.clone();
+          sorted_expected.sort_by_key(|&(x, y)| (y, x));
+          assert_eq!(sorted_expected, actual);
+          assert_eq!(
+            !expected.is_empty(),
+            tree.any_in_range(min_x..=max_x, min_y..=max_y)?
+          );
+        }
+      }
+    }
+    let tree = K2Tree::test_tree(2);
+    /* Only the axes that are out of bounds are reported past the edge */
+    let out_of_bounds = |x_y| Error::Read {
+      source: Box::new(Error::OutOfBounds { x_y, min_x_y: [0, 0], max_x_y: [7, 7] })
+    };
+    assert_eq!(out_of_bounds([8, 0]), tree.ones_in_range(0..=8, 0..=7).unwrap_err());
+    assert_eq!(out_of_bounds([0, 8]), tree.any_in_range(0..=7, 3..=8).unwrap_err());
+    assert_eq!(out_of_bounds([9, 8]), tree.count_in_range(2..=9, 3..=8).unwrap_err());
+    Ok(())
+  }
+  #[test]
+  fn count_ones() -> Result<()> {
+    assert_eq!(0, K2Tree::new().count_ones());
+    assert_eq!(9, K2Tree::test_tree(2).count_ones());
+    assert_eq!(12, K2Tree::test_tree(3).count_ones());
+    Ok(())
+  }
+  #[test]
+  fn count_in_range() -> Result<()> {
+    let ranges = [
+      [0, 26, 0, 26], [0, 7, 0, 7], [4, 7, 0, 3], [3, 12, 1, 9],
+      [9, 9, 0, 26], [0, 26, 18, 18], [9, 17, 0, 8], [5, 4, 0, 26],
+    ];
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let matrix = K2Tree::test_matrix(3);
+        let tree = K2Tree::from_matrix(matrix.clone(), stem_k, leaf_k)?;
+        for &[min_x, max_x, min_y, max_y] in ranges.iter() {
+          let mut expected = 0;
+          for y in min_y..=max_y {
+            for x in min_x..=max_x {
+              if matrix.get(x, y)? { expected += 1; }
+            }
+          }
+          assert_eq!(expected, tree.count_in_range(min_x..=max_x, min_y..=max_y)?);
+        }
+        let [max_x, max_y] = [tree.width()-1, tree.height()-1];
+        assert_eq!(tree.count_ones(), tree.count_in_range(0..=max_x, 0..=max_y)?);
+      }
+    }
+    assert!(K2Tree::test_tree(2).count_in_range(0..=8, 0..=7).is_err());
+    Ok(())
+  }
+  #[test]
   fn set_0() -> Result<()> {
     let mut tree = K2Tree::with_k(2, 2)?;
     assert_eq!(false, tree.get(0, 0).unwrap());
@@ -1362,6 +2370,80 @@ mod api {
     Ok(())
   }
   #[test]
+  fn set_many_0() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for k in 2..4 {
+      let mut tree = K2Tree::test_tree(k);
+      let mut expected = tree.clone();
+      let width = tree.matrix_width();
+      let updates: Vec<(usize, usize, bool)> = (0..100)
+        .map(|_| (rng.gen_range(0, width), rng.gen_range(0, width), rng.gen()))
+        .collect();
+      for &(x, y, state) in updates.iter() {
+        expected.set(x, y, state)?;
+      }
+      /* Bits set more than once only count once, if at all */
+      let mut changed_bits = 0;
+      for y in 0..width {
+        for x in 0..width {
+          if expected.get(x, y)? != K2Tree::test_tree(k).get(x, y)? { changed_bits += 1; }
+        }
+      }
+      assert_eq!(changed_bits, tree.set_many(updates)?);
+      assert_eq!(expected, tree);
+      tree.validate()?;
+    }
+    /* Stem-layers with different k values */
+    let mut tree = K2Tree::with_stem_ks(&[4, 3, 2], 2)?;
+    let mut expected = tree.clone();
+    for _ in 0..5 {
+      let updates: Vec<(usize, usize, bool)> = (0..50)
+        .map(|_| (rng.gen_range(0, 48), rng.gen_range(0, 48), rng.gen_bool(0.7)))
+        .collect();
+      for &(x, y, state) in updates.iter() {
+        expected.set(x, y, state)?;
+      }
+      tree.set_many(updates)?;
+      assert_eq!(expected, tree);
+      tree.validate()?;
+    }
+    Ok(())
+  }
+  #[test]
+  fn set_many_1() -> Result<()> {
+    let mut tree = K2Tree::test_tree(2);
+    assert_eq!(0, tree.set_many(vec![(5, 0, true), (0, 0, false), (100, 100, false)])?);
+    assert_eq!(K2Tree::test_tree(2), tree);
+    assert_eq!(2, tree.set_many(vec![(5, 0, false), (40, 3, true)])?);
+    assert_eq!(64, tree.matrix_width());
+    let coords = [(7,0),(4,1),(7,1),(6,2),(7,2),(0,4),(5,4),(4,5),(40,3)];
+    assert_eq!(K2Tree::from_coords(coords.iter().cloned(), 2, 2)?, tree);
+    tree.validate()?;
+    assert_eq!(9, tree.set_many(coords.iter().map(|&(x, y)| (x, y, false)))?);
+    assert!(tree.is_empty());
+    assert_eq!(64, tree.matrix_width());
+    tree.validate()?;
+    let mut grown = K2Tree::with_k(2, 2)?;
+    grown.set_many(vec![(63, 63, true)])?;
+    assert_eq!(K2Tree::from_coords(vec![(63, 63)], 2, 2)?, grown);
+    grown.validate()?;
+    /* Growing by several stem-layers at once is the same as one at a time */
+    grown.set_many(vec![(1000, 2, true)])?;
+    let mut expected = K2Tree::from_coords(vec![(63, 63)], 2, 2)?;
+    for _ in 0..4 { expected.grow(); }
+    expected.set(1000, 2, true)?;
+    assert_eq!(expected, grown);
+    grown.validate()?;
+    /* The largest coordinate needs a matrix_width too wide for a usize */
+    assert!(matches!(
+      tree.set_many(vec![(usize::MAX, 0, true)]),
+      Err(Error::OversizedMatrix{..})
+    ));
+    assert!(tree.is_empty());
+    Ok(())
+  }
+  #[test]
   fn matrix_width_and_grow_0() -> Result<()> {
     for k in 2..9usize {
       let k_cubed = k.pow(3);
@@ -1635,6 +2717,43 @@ mod api {
     Ok(())
   }
   #[test]
+  fn from_coords() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let coords: Vec<(usize, usize)> = (0..200)
+          .map(|_| (rng.gen_range(0, 100), rng.gen_range(0, 100)))
+          .collect();
+        let tree = K2Tree::from_coords(coords.clone(), stem_k, leaf_k)?;
+        let mut matrix = BitMatrix::with_dimensions(tree.matrix_width(), tree.matrix_width());
+        for &(x, y) in coords.iter() {
+          matrix.set(x, y, true)?;
+        }
+        assert_eq!(matrix, tree.to_matrix()?);
+        assert_eq!(tree.build_layer_starts(), tree.layer_starts());
+      }
+    }
+    assert_eq!(K2Tree::with_k(3, 2)?, K2Tree::from_coords(Vec::new(), 3, 2)?);
+    assert!(K2Tree::from_coords(vec![(0, 0)], 1, 2).is_err());
+    assert!(matches!(
+      K2Tree::from_coords(vec![(usize::MAX, 0), (1, 1)], 2, 2),
+      Err(Error::OversizedMatrix{..})
+    ));
+    assert!(matches!(
+      K2Tree::from_coords(vec![(0, usize::MAX - 1)], 3, 3),
+      Err(Error::OversizedMatrix{..})
+    ));
+    Ok(())
+  }
+  #[test]
+  fn from_iter() -> Result<()> {
+    let tree: K2Tree = vec![(5, 0), (7, 0), (4, 1), (7, 1), (6, 2), (7, 2),
+      (0, 4), (5, 4), (4, 5)].into_iter().collect();
+    assert_eq!(K2Tree::test_tree(2), tree);
+    Ok(())
+  }
+  #[test]
   fn to_matrix() -> Result<()> {
     for k in 2..=3usize {
       let tree = K2Tree::test_tree(k);
@@ -1642,6 +2761,12 @@ mod api {
       assert_eq!(matrix, tree.to_matrix()?);
       assert_eq!(matrix, K2Tree::from_matrix(matrix.clone(), k, k)?.to_matrix()?);
     }
+    let mut matrix = BitMatrix::with_dimensions(5, 11);
+    matrix.set(4, 10, true)?;
+    matrix.set(1, 3, true)?;
+    let tree = K2Tree::from_matrix(matrix.clone(), 2, 2)?;
+    assert_eq!([5, 11, 16], [tree.width(), tree.height(), tree.matrix_width()]);
+    assert_eq!(matrix, tree.to_matrix()?);
     Ok(())
   }
   #[test]
@@ -1676,18 +2801,6 @@ mod util {
     assert_eq!(vec![1,3,5,9], one_positions(bv.into_iter()));
   }
   #[test]
-  fn ones_in_range_0() {
-    let ranges = [
-      bitvec![0,1,1,1,0,0,1,0,1,1,0,0],
-      bitvec![0,0,0,0,0,0,1],
-      bitvec![0,1,1,1,1,1,1,0,1,0,0,1]
-    ];
-    let num_ones = [6, 1, 8];
-    for i in 0..ranges.len() {
-      assert_eq!(ones_in_range(&ranges[i], 0, ranges[i].len()), num_ones[i]);
-    }
-  }
-  #[test]
   fn stem_layer_start_0() {
     let tree = K2Tree::test_tree(2);
     assert_eq!(tree.layer_start(0), 0);
@@ -1765,11 +2878,11 @@ mod util {
   fn get_coords_1() -> std::result::Result<(), serde_json::Error> {
     // This huge K2Tree is from an error case I found in the wild,
     // using it as proof the bug was fixed in this test.
-    let leaves = K2Tree {
-      stem_k: 2,
-      leaf_k: 2,
-      max_slayers: 11,
-      stems: bitvec![
+    let leaves = K2Tree::from_bits(
+      2,
+      2,
+      11,
+      bitvec![
           1,1,1,0,1,1,1,1,0,0,1,0,1,1,1,0,1,1,0,0,1,1,0,0,0,0,1,0,0,0,0,1,0,0,1,0,1,1,0,0,1,1,0,0,1,0,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,0,0,0,
           1,0,1,0,0,0,0,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,0,1,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,0,0,0,1,0,
           1,0,0,0,1,1,0,0,1,1,0,0,1,0,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,0,0,0,0,0,1,0,1,0,1,0,1,0,0,0,1,1,0,0,
@@ -1866,7 +2979,7 @@ mod util {
           0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,
           0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,0,1,1,0,0,1,1,0,0,1,1,0,0,1,0,0,0,
       ],
-      leaves: bitvec![
+      bitvec![
           0,0,1,0,0,0,0,1,0,0,1,1,0,0,0,1,1,1,0,0,1,0,0,0,0,1,0,0,0,0,1,0,0,0,1,0,0,0,1,0,0,0,1,0,0,0,1,0,0,0,0,1,0,0,1,0,0,0,1,0,0,0,1,0,
           0,0,0,1,0,0,1,0,0,0,0,1,0,0,0,1,0,0,1,0,0,0,1,0,0,0,0,1,0,0,0,1,0,0,1,0,0,0,0,1,0,0,1,0,0,0,0,1,0,0,1,0,0,0,0,1,0,0,1,0,0,0,1,0,
           0,0,1,0,0,0,0,1,0,0,0,1,0,0,0,1,0,0,1,0,0,0,0,1,0,0,0,1,0,0,0,1,0,0,0,1,0,0,1,0,0,0,0,1,0,0,0,1,0,0,1,0,0,0,0,1,0,0,0,1,0,0,1,0,
@@ -1915,8 +3028,8 @@ mod util {
           1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,
           1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,
           1,1,0,0,1,1,0,0,0,0,0,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,
-      ],
-    }.into_leaves();
+      ]
+    ).into_leaves();
 
     let mut x = 0;
     for _leaf in leaves { x += 1; } // Do a cheap op to make sure the value from leaves is assigned
@@ -1956,6 +3069,117 @@ mod util {
     }
     Ok(())
   }
+  #[test]
+  fn layer_starts_kept_up_to_date() -> Result<()> {
+    let mut tree = K2Tree::with_k(2, 2)?;
+    tree.grow();
+    tree.grow();
+    assert_eq!(tree.build_layer_starts(), tree.layer_starts());
+    let coords = [[0, 0], [31, 31], [5, 17], [6, 17], [30, 2]];
+    for &[x, y] in coords.iter() {
+      tree.set(x, y, true)?;
+      assert_eq!(tree.build_layer_starts(), tree.layer_starts());
+    }
+    tree.grow();
+    assert_eq!(tree.build_layer_starts(), tree.layer_starts());
+    for &[x, y] in coords.iter() {
+      tree.set(x, y, false)?;
+      assert_eq!(tree.build_layer_starts(), tree.layer_starts());
+    }
+    tree.shrink()?;
+    assert_eq!(tree.build_layer_starts(), tree.layer_starts());
+    tree.set(3, 3, true)?;
+    tree.shrink()?;
+    assert_eq!(tree.build_layer_starts(), tree.layer_starts());
+    assert!(tree.get(3, 3)?);
+    Ok(())
+  }
+  #[test]
+  fn with_stem_ks() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for stem_ks in [vec![4, 2, 2], vec![3, 2], vec![2, 3, 4], vec![5]].iter() {
+      let mut tree = K2Tree::with_stem_ks(stem_ks, 2)?;
+      let width = tree.matrix_width();
+      assert_eq!(2 * stem_ks.iter().product::<usize>(), width);
+      let coords: Vec<(usize, usize)> = (0..100)
+        .map(|_| (rng.gen_range(0, width), rng.gen_range(0, width)))
+        .collect();
+      for &(x, y) in coords.iter() {
+        tree.set(x, y, true)?;
+        tree.validate()?;
+      }
+      let mut expected = coords.clone();
+      expected.sort_by_key(|&(x, y)| (y, x));
+      expected.dedup();
+      let range_max = width-1;
+      let mut ones = tree.ones_in_range(0..=range_max, 0..=range_max)?;
+      ones.sort_by_key(|&(x, y)| (y, x));
+      assert_eq!(expected, ones);
+      let mut ones: Vec<(usize, usize)> = tree.leaves().filter(|bit| bit.value).map(|bit| (bit.x, bit.y)).collect();
+      ones.sort_by_key(|&(x, y)| (y, x));
+      assert_eq!(expected, ones);
+      assert_eq!(tree.stems.len(), tree.stems().count());
+      assert_eq!(stem_ks.len(), tree.stems().last().unwrap().layer + 1);
+      /* Building from the same 1s gives the same tree */
+      let mut built = K2Tree::with_stem_ks(stem_ks, 2)?;
+      built.set_many(coords.iter().map(|&(x, y)| (x, y, true)).collect::<Vec<_>>())?;
+      assert_eq!(tree, built);
+      for &(x, y) in coords.iter() {
+        tree.set(x, y, false)?;
+        tree.validate()?;
+      }
+      assert!(tree.is_empty());
+      assert_eq!(stem_ks.to_vec(), tree.stem_ks());
+    }
+    assert_eq!(Err(Error::SmallStemKValue { stem_k: 1 }), K2Tree::with_stem_ks(&[4, 1], 2));
+    assert_eq!(Err(Error::from(StructureError::NoStemLayers)), K2Tree::with_stem_ks(&[], 2));
+    assert_eq!(
+      Err(Error::OversizedMatrix { stem_ks: vec![1 << 33, 1 << 33], leaf_k: 2 }),
+      K2Tree::with_stem_ks(&[1 << 33, 1 << 33], 2)
+    );
+    /* The same k for every stem-layer is the same as with_k */
+    assert_eq!(K2Tree::with_k(3, 2)?, K2Tree::with_stem_ks(&[3, 3], 2)?);
+    Ok(())
+  }
+  #[test]
+  fn with_stem_ks_resize() -> Result<()> {
+    let mut tree = K2Tree::with_stem_ks(&[3, 2], 2)?;
+    let coords = [(0, 0), (11, 11), (5, 7), (6, 2)];
+    for &(x, y) in coords.iter() {
+      tree.set(x, y, true)?;
+    }
+    tree.grow();
+    assert_eq!(vec![3, 3, 2], tree.stem_ks());
+    assert_eq!(36, tree.matrix_width());
+    tree.validate()?;
+    tree.set(35, 35, true)?;
+    assert!(tree.shrink().is_err());
+    tree.set(35, 35, false)?;
+    tree.shrink()?;
+    assert_eq!(vec![3, 2], tree.stem_ks());
+    tree.validate()?;
+    assert!(tree.get(11, 11)?);
+    /* Set the 1s outside of the smaller matrix again */
+    let mut tree = K2Tree::with_stem_ks(&[3, 2], 2)?;
+    tree.set_many(coords.iter().map(|&(x, y)| (x, y, true)).collect::<Vec<_>>())?;
+    tree.set_many(vec![(20, 3, true)])?;
+    assert_eq!(vec![3, 3, 2], tree.stem_ks());
+    /* No number of stem-layers fits a 1 at the largest coordinate */
+    assert!(matches!(
+      tree.clone().set_many(vec![(usize::MAX, 0, true)]),
+      Err(Error::OversizedMatrix{..})
+    ));
+    tree.set_leaf_k(3)?;
+    assert_eq!(vec![3, 3, 2], tree.stem_ks());
+    assert_eq!(5, tree.count_ones());
+    tree.validate()?;
+    tree.set_stem_k(2)?;
+    assert_eq!(vec![2; 5], tree.stem_ks());
+    assert_eq!(5, tree.count_ones());
+    assert!(tree.get(20, 3)?);
+    Ok(())
+  }
 }
 
 #[cfg(test)]
@@ -2072,11 +3296,77 @@ mod ser_de {
     Ok(())
   }
   #[test]
-  fn deserialize_json_2() -> std::result::Result<(), serde_json::Error> {
-    let expected = K2Tree::test_tree(4);
+  fn deserialize_json_2() {
+    /* test_tree(4)'s second stem-layer has 12 1s but it only has 7 leaves */
     let json = r#"{"stemK":4,"leafK":4,"maxStemLayers":2,"stems":[13835058330193736073,2251825046626304],"leaves":[2269392002875393,140737488887808]}"#;
-    let actual: K2Tree = serde_json::from_str(json)?;
-    assert_eq!(actual, expected);
+    let actual = serde_json::from_str::<K2Tree>(json);
+    let expected = Error::from(StructureError::LeafCount { expected: 12, found: 7 });
+    assert!(actual.unwrap_err().to_string().starts_with(&expected.to_string()));
+  }
+  #[test]
+  fn deserialize_json_empty() -> std::result::Result<(), serde_json::Error> {
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let expected = K2Tree::with_k(stem_k, leaf_k).unwrap();
+        let json = serde_json::to_string(&expected)?;
+        let actual: K2Tree = serde_json::from_str(&json)?;
+        assert_eq!(actual, expected);
+      }
+    }
+    Ok(())
+  }
+  #[test]
+  fn deserialize_json_invalid() {
+    /* serde_json appends the position of the error to its message */
+    let error = |json: &str| {
+      let message = serde_json::from_str::<K2Tree>(json).unwrap_err().to_string();
+      message[..message.find(" at line").unwrap()].to_string()
+    };
+    let expected = |error: StructureError| Error::from(error).to_string();
+    assert_eq!(
+      expected(StructureError::SmallStemKValue { stem_k: 1 }),
+      error(r#"{"stemK":1,"leafK":2,"maxStemLayers":2,"stems":[4542],"leaves":[398246]}"#)
+    );
+    assert_eq!(
+      expected(StructureError::SmallLeafKValue { leaf_k: 0 }),
+      error(r#"{"stemK":2,"leafK":0,"maxStemLayers":2,"stems":[4542],"leaves":[398246]}"#)
+    );
+    /* test_tree(2) with a third stem-layer */
+    assert_eq!(
+      expected(StructureError::StemCount { layer: 2, expected: 5, found: 0 }),
+      error(r#"{"stemK":2,"leafK":2,"maxStemLayers":3,"stems":[4542],"leaves":[398246]}"#)
+    );
+    /* test_tree(2) with an extra 1 in its last stem */
+    assert_eq!(
+      expected(StructureError::LeafCount { expected: 6, found: 5 }),
+      error(r#"{"stemK":2,"leafK":2,"maxStemLayers":2,"stems":[37310],"leaves":[398246]}"#)
+    );
+    /* test_tree(2) with its first leaf cleared */
+    assert_eq!(
+      expected(StructureError::EmptyLeaf { leaf: 0 }),
+      error(r#"{"stemK":2,"leafK":2,"maxStemLayers":2,"stems":[4542],"leaves":[398240]}"#)
+    );
+    assert_eq!(
+      expected(StructureError::OversizedBlock { k: 8589934592 }),
+      error(r#"{"stemK":8589934592,"leafK":2,"maxStemLayers":1,"stems":[0],"leaves":[]}"#)
+    );
+    assert_eq!(
+      expected(StructureError::StemKsMismatch { stem_k: 2, max_slayers: 2, stem_ks: vec![3, 2] }),
+      error(r#"{"stemK":2,"leafK":2,"maxStemLayers":2,"stems":[4542],"leaves":[398246],"stemKs":[3,2]}"#)
+    );
+  }
+  #[test]
+  fn serialize_json_stem_ks() -> std::result::Result<(), serde_json::Error> {
+    let mut tree = K2Tree::with_stem_ks(&[4, 2, 3], 2).unwrap();
+    for &(x, y) in [(0, 0), (47, 47), (13, 30), (40, 2), (41, 2)].iter() {
+      tree.set(x, y, true).unwrap();
+    }
+    let json = serde_json::to_string(&tree)?;
+    assert!(json.ends_with(r#""stemKs":[4,2,3]}"#));
+    assert_eq!(tree, serde_json::from_str(&json)?);
+    /* Uniform K2Trees leave the k value of each stem-layer out */
+    let json = serde_json::to_string(&K2Tree::test_tree(2))?;
+    assert!(!json.contains("stemKs"));
     Ok(())
   }
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
-use bitvec::vec::BitVec;
+use bitvec::prelude::{BitOrder, BitStore, BitVec, Local};
 use crate::tree::datastore::K2Tree;
+use crate::tree::{Layout, Range2D};
 
 /// A struct representing the value of a bit in a K2Tree's stems.
 /// 
@@ -49,10 +50,10 @@ pub struct Stems<'a> {
 impl<'a> Iterator for Stems<'a> {
   type Item = StemBit;
   fn next(&mut self) -> Option<Self::Item> {
-    let stem_len = self.tree.stem_len();
     if self.pos >= self.tree.stems.len() {
       return None
     }
+    let stem_len = self.tree.slayer_len(self.layer);
     /* Grab the return value */
     let ret_v = Some(StemBit {
       value: self.tree.stems[self.pos],
@@ -103,10 +104,10 @@ pub struct IntoStems {
 impl Iterator for IntoStems {
   type Item = StemBit;
   fn next(&mut self) -> Option<Self::Item> {
-    let stem_len = self.tree.stem_len();
     if self.pos >= self.tree.stems.len() {
       return None
     }
+    let stem_len = self.tree.slayer_len(self.layer);
     /* Grab the return value */
     let ret_v = Some(StemBit {
       value: self.tree.stems[self.pos],
@@ -267,3 +268,165 @@ impl<'a> LeavesRaw<'a> {
     }
   }
 }
+
+/* A part of the tree waiting to be visited by RangeOnes */
+#[derive(Debug)]
+enum Node {
+  Stem {
+    layer: usize,
+    stem_start: usize,
+    range: Range2D,
+  },
+  Leaf {
+    leaf_start: usize,
+    range: Range2D,
+  },
+  Bit(usize, usize),
+}
+
+/// An iterator over the coordinates of the 1s inside a rectangular range of the matrix
+/// a K2Tree represents, which produces (x, y) pairs.
+/// 
+/// Only the parts of the K2Tree that overlap the range are ever visited.
+#[derive(Debug)]
+pub struct RangeOnes<'a, O = Local, T = usize>
+where O: BitOrder, T: BitStore {
+  layout: Layout<'a, O, T>,
+  query: Range2D,
+  stack: Vec<Node>,
+}
+impl<'a, O: BitOrder, T: BitStore> Iterator for RangeOnes<'a, O, T> {
+  type Item = (usize, usize);
+  fn next(&mut self) -> Option<Self::Item> {
+    let layout = &self.layout;
+    while let Some(node) = self.stack.pop() {
+      match node {
+        Node::Bit(x, y) => return Some((x, y)),
+        Node::Leaf{leaf_start, range} => {
+          /* Push in reverse so the bits are popped in order */
+          let leaf_k = layout.leaf_k;
+          for offset in (0..layout.leaf_len()).rev() {
+            let x = range.min_x + (offset % leaf_k);
+            let y = range.min_y + (offset / leaf_k);
+            if layout.leaves[leaf_start+offset] && self.query.contains(x, y) {
+              self.stack.push(Node::Bit(x, y));
+            }
+          }
+        },
+        Node::Stem{layer, stem_start, range} => {
+          let stem_k = layout.slayer_k(layer);
+          for child_pos in (0..stem_k*stem_k).rev() {
+            let bit_pos = stem_start+child_pos;
+            let child_range = range.child(stem_k, child_pos);
+            if !layout.stems[bit_pos] || !child_range.intersects(&self.query) {
+              continue
+            }
+            if layer == layout.max_slayers-1 {
+              self.stack.push(Node::Leaf {
+                leaf_start: layout.leaf_start(bit_pos),
+                range: child_range,
+              });
+            }
+            else {
+              self.stack.push(Node::Stem {
+                layer: layer+1,
+                stem_start: layout.child_start(layer, bit_pos),
+                range: child_range,
+              });
+            }
+          }
+        },
+      }
+    }
+    None
+  }
+}
+impl<'a> RangeOnes<'a> {
+  /// Produces a RangeOnes iterator over the range `[min_x..=max_x] × [min_y..=max_y]`
+  /// from a reference to a K2Tree.
+  /// 
+  /// Produces nothing if the range is empty or outside of the matrix.
+  pub fn new(tree: &'a K2Tree, min_x: usize, max_x: usize, min_y: usize, max_y: usize) -> Self {
+    RangeOnes::from_layout(tree.layout(), Range2D::new(min_x, max_x, min_y, max_y))
+  }
+}
+impl<'a, O: BitOrder, T: BitStore> RangeOnes<'a, O, T> {
+  pub(super) fn from_layout(layout: Layout<'a, O, T>, query: Range2D) -> Self {
+    let mut stack = Vec::new();
+    if query.min_x <= query.max_x && query.min_y <= query.max_y {
+      let range_max = layout.matrix_width()-1;
+      stack.push(Node::Stem {
+        layer: 0,
+        stem_start: 0,
+        range: Range2D::new(0, range_max, 0, range_max),
+      });
+    }
+    Self {
+      layout,
+      query,
+      stack,
+    }
+  }
+}
+
+/// An iterator over the x-coordinates of the 1s in a row of the matrix a K2Tree represents.
+/// 
+/// Only the parts of the K2Tree that overlap the row are ever visited.
+#[derive(Debug)]
+pub struct Successors<'a, O = Local, T = usize>
+where O: BitOrder, T: BitStore {
+  ones: RangeOnes<'a, O, T>,
+}
+impl<'a, O: BitOrder, T: BitStore> Iterator for Successors<'a, O, T> {
+  type Item = usize;
+  fn next(&mut self) -> Option<Self::Item> {
+    self.ones.next().map(|(x, _)| x)
+  }
+}
+impl<'a> Successors<'a> {
+  /// Produces a Successors iterator over row `y` from a reference to a K2Tree.
+  /// 
+  /// Produces nothing if `y` is outside of the matrix.
+  pub fn new(tree: &'a K2Tree, y: usize) -> Self {
+    Successors::from_layout(tree.layout(), y)
+  }
+}
+impl<'a, O: BitOrder, T: BitStore> Successors<'a, O, T> {
+  pub(super) fn from_layout(layout: Layout<'a, O, T>, y: usize) -> Self {
+    let range_max = layout.matrix_width()-1;
+    Self {
+      ones: RangeOnes::from_layout(layout, Range2D::new(0, range_max, y, y)),
+    }
+  }
+}
+
+/// An iterator over the y-coordinates of the 1s in a column of the matrix a K2Tree represents.
+/// 
+/// Only the parts of the K2Tree that overlap the column are ever visited.
+#[derive(Debug)]
+pub struct Predecessors<'a, O = Local, T = usize>
+where O: BitOrder, T: BitStore {
+  ones: RangeOnes<'a, O, T>,
+}
+impl<'a, O: BitOrder, T: BitStore> Iterator for Predecessors<'a, O, T> {
+  type Item = usize;
+  fn next(&mut self) -> Option<Self::Item> {
+    self.ones.next().map(|(_, y)| y)
+  }
+}
+impl<'a> Predecessors<'a> {
+  /// Produces a Predecessors iterator over column `x` from a reference to a K2Tree.
+  /// 
+  /// Produces nothing if `x` is outside of the matrix.
+  pub fn new(tree: &'a K2Tree, x: usize) -> Self {
+    Predecessors::from_layout(tree.layout(), x)
+  }
+}
+impl<'a, O: BitOrder, T: BitStore> Predecessors<'a, O, T> {
+  pub(super) fn from_layout(layout: Layout<'a, O, T>, x: usize) -> Self {
+    let range_max = layout.matrix_width()-1;
+    Self {
+      ones: RangeOnes::from_layout(layout, Range2D::new(x, x, 0, range_max)),
+    }
+  }
+}
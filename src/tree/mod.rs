@@ -1,6 +1,12 @@
 
+mod binary;
+mod builder;
 mod datastore;
 mod iterators;
+mod ops;
+mod rank;
+mod validate;
+mod view;
 
 pub use datastore::*;
 pub use datastore::K2Tree;
@@ -12,10 +18,16 @@ pub use iterators::{
   Leaves,
   IntoLeaves,
   LeavesRaw,
+  Successors,
+  Predecessors,
+  RangeOnes,
 };
 
 /* Private Yet Common to Everything Module */
-use bitvec::prelude::BitVec;
+use bitvec::prelude::{BitOrder, BitSlice, BitStore, BitVec, Local};
+pub use builder::K2TreeBuilder;
+pub use view::{K2TreeView, ViewLeaves};
+use rank::RankSelect;
 
 impl K2Tree {
   // Internal: Never call with layer >= self.max_slayers
@@ -27,124 +39,304 @@ impl K2Tree {
     layer_starts[l+1] - layer_starts[l]
   }
   fn get_coords(&self, leaf_bit_pos: usize) -> [usize; 2] {
-    /* Start at the leaf_bit and traverse our way up to the top of the tree,
-    keeping track of the path we took on our way up in terms of
-    bit-positions (offsets) in the stems. Then, traverse back down the same
-    path to find the coords of the leaf_bit. */
-    let parent_bit = self.leaf_parent(leaf_bit_pos);
-    let mut stem_start = self.stem_start(parent_bit);
-    let mut offset = parent_bit - stem_start;
-    let mut offsets = vec![offset];
-    for _ in 1..self.max_slayers {
-      let parent = self.parent(stem_start).unwrap();
-      stem_start = parent[0];
-      offset = parent[1];
-      offsets.push(offset);
-    }
-    /* Reverse the offsets ready to traverse them back down the tree */
-    offsets.reverse();
-    let range_max = self.matrix_width()-1;
-    let mut range = Range2D::new(0, range_max, 0, range_max);
-    for child_offset in offsets.into_iter().take(self.max_slayers) {
-      range = self.to_subranges(range).unwrap()[child_offset];
-    }
-    let leaf_offset = leaf_bit_pos - self.leaf_start(leaf_bit_pos);
-    let x = leaf_offset % self.leaf_k;
-    let y = leaf_offset / self.leaf_k;
-    [range.min_x + x, range.min_y + y]
+    self.layout().get_coords(leaf_bit_pos)
   }
   fn leaf_parent(&self, bit_pos: usize) -> usize {
+    /* The first 1s in the stems point to the other stems,
+    the rest point to the leaves in order */
     let nth_leaf = bit_pos / self.leaf_len();
-    let final_stem_layer_start = self.layer_start(self.max_slayers-1);
-    let stem_ones_positions = one_positions_range(
-      &self.stems,
-      final_stem_layer_start,
-      self.stems.len()
-    );
-    final_stem_layer_start + stem_ones_positions[nth_leaf] //TODO: check
+    self.stem_ranks.select(&self.stems, self.stems_after_root() + nth_leaf).unwrap()
   }
   fn parent(&self, stem_start: usize) -> std::result::Result<[usize; 2], ()> {
     /* Returns [stem_start, bit_offset] */
-    let stem_len = self.stem_len();
-    if stem_start < stem_len {
+    let layer = self.slayer_of(stem_start);
+    if layer == 0 {
       return Err(()) //First stem cannot have parent
     }
-    /* Find which layer stem_start is in */
-    let layer_starts = self.layer_starts();
-    let stem_layer = {
-      let mut layer = self.max_slayers-1; //If no match, must be in highest layer
-      for (i, &layer_start) in layer_starts.iter().enumerate() {
-        if stem_start < layer_start { layer = i-1; break }
-      }
-      layer
+    /* The nth stem in a layer is pointed to by the nth 1 in the layer above */
+    let nth_stem = (stem_start - self.layer_start(layer)) / self.slayer_len(layer);
+    let parent_rank = self.stem_ranks.rank(&self.stems, self.layer_start(layer-1)) + nth_stem;
+    let parent_bit_absolute_pos = match self.stem_ranks.select(&self.stems, parent_rank) {
+      Some(pos) => pos,
+      None => return Err(()),
     };
-    /* Find the nth stem it is in the layer */
-    let stem_num = (stem_start - layer_starts[stem_layer]) / stem_len;
-    /* Find the nth 1 in the parent layer */
-    let parent_bit_relative_pos = one_positions_range(
-      &self.stems,
-      layer_starts[stem_layer-1],
-      layer_starts[stem_layer]
-    )[stem_num];
-    let parent_bit_absolute_pos = parent_bit_relative_pos + layer_starts[stem_layer-1];
-    Ok([self.stem_start(parent_bit_absolute_pos), parent_bit_absolute_pos % stem_len])
+    let parent_stem_start = self.stem_start(layer-1, parent_bit_absolute_pos);
+    Ok([parent_stem_start, parent_bit_absolute_pos - parent_stem_start])
   }
-  fn layer_start(&self, l: usize) -> usize {
-    //Private method, let it crash seeing as is just unwrapped otherwise
-    let mut curr_layer = 1;
-    let mut layer_starts = vec![0, self.stem_len()];
-    while curr_layer < l {
-      let stems_in_curr_layer = ones_in_range(
-        &self.stems,
-        layer_starts[curr_layer-1],
-        layer_starts[curr_layer]
-      );
-      let curr_layer_len = stems_in_curr_layer * self.stem_len();
-      layer_starts.push(layer_starts[curr_layer] + curr_layer_len);
-      curr_layer += 1;
+  fn stem_to_leaf_start(&self, stem_bitpos: usize) -> std::result::Result<usize, ()> {
+    if !self.stems[stem_bitpos] { Err(()) }
+    else {
+      Ok(self.layout().leaf_start(stem_bitpos))
     }
-    layer_starts[l]
-  }
-  fn layer_starts(&self) -> Vec<usize> {
-    //Private method, let it crash seeing as is just unwrapped otherwise
-    let mut curr_layer = 1;
-    let mut layer_starts = vec![0, self.stem_len()];
-    while curr_layer < self.max_slayers-1 {
-      let stems_in_curr_layer = ones_in_range(
-        &self.stems,
-        layer_starts[curr_layer-1],
-        layer_starts[curr_layer]
-      );
-      let curr_layer_len = stems_in_curr_layer * self.stem_len();
-      layer_starts.push(layer_starts[curr_layer] + curr_layer_len);
-      curr_layer += 1;
+  }
+  fn child_stem(&self, layer: usize, stem_start: usize, nth_child: usize) -> std::result::Result<usize, ()> {
+    if !self.stems[stem_start+nth_child]
+    || layer == self.max_slayers-1 {
+      /* If stem_bit is 0 or final stem layer, cannot have children */
+      return Err(())
+    }
+    Ok(self.child_start(layer, stem_start+nth_child))
+  }
+  /* The start of the stem in the next layer pointed to by the bit at `bit_pos`, or
+  where it would be if the bit were a 1 */
+  fn child_start(&self, layer: usize, bit_pos: usize) -> usize {
+    self.layout().child_start(layer, bit_pos)
+  }
+  fn stems_after_root(&self) -> usize {
+    self.layout().stems_after_root()
+  }
+  fn subtree_ones(&self, layer: usize, bit_pos: usize) -> usize {
+    /* The descendants of a run of stem bits are a run of blocks in the
+    next layer, so follow the run down to the leaves and count them */
+    let mut begin = bit_pos;
+    let mut end = bit_pos+1;
+    for l in layer..self.max_slayers-1 {
+      begin = self.child_start(l, begin);
+      end = self.child_start(l, end);
+    }
+    let stems_after_root = self.stems_after_root();
+    let leaf_begin = (self.stem_ranks.rank(&self.stems, begin) - stems_after_root) * self.leaf_len();
+    let leaf_end = (self.stem_ranks.rank(&self.stems, end) - stems_after_root) * self.leaf_len();
+    self.leaf_ranks.rank(&self.leaves, leaf_end) - self.leaf_ranks.rank(&self.leaves, leaf_begin)
+  }
+  fn layer_start(&self, l: usize) -> usize {
+    self.slayer_starts[l]
+  }
+  fn layer_starts(&self) -> &[usize] {
+    &self.slayer_starts
+  }
+  fn build_layer_starts(&self) -> Vec<usize> {
+    /* Every 1 before the start of a layer points to a stem in the
+    layers up to and including the next one */
+    let mut layer_starts = vec![0];
+    for l in 1..std::cmp::max(self.max_slayers, 2) {
+      let prev_start = layer_starts[l-1];
+      let layer_start = match self.stem_ks {
+        None => (self.stem_ranks.rank(&self.stems, prev_start) + 1) * self.stem_len(),
+        /* Each layer has one stem for each 1 in the layer above it */
+        Some(_) => {
+          let prev_stems = if l == 1 { 1 }
+            else { self.stem_ranks.rank(&self.stems, prev_start) - self.stem_ranks.rank(&self.stems, layer_starts[l-2]) };
+          prev_start + prev_stems * self.slayer_len(l-1)
+        },
+      };
+      layer_starts.push(layer_start);
     }
     layer_starts
   }
+  /* Updates the layer offsets after a stem is inserted into or removed from a layer,
+  which moves the start of every layer after it */
+  fn stem_inserted(&mut self, layer: usize) {
+    let stem_len = self.slayer_len(layer);
+    for layer_start in self.slayer_starts[layer+1..].iter_mut() {
+      *layer_start += stem_len;
+    }
+  }
+  fn stem_removed(&mut self, layer: usize) {
+    let stem_len = self.slayer_len(layer);
+    for layer_start in self.slayer_starts[layer+1..].iter_mut() {
+      *layer_start -= stem_len;
+    }
+  }
+  fn rank_stems(&mut self) {
+    self.stem_ranks = RankSelect::new(&self.stems);
+  }
+  /* The number of stem-layers a K2Tree needs for its matrix to contain the coordinate
+  `max_coord`, which is one less than the width of the matrix it must represent so
+  that the largest coordinate always fits in a usize */
+  fn slayers_to_fit(max_coord: usize, stem_k: usize, leaf_k: usize) -> std::result::Result<usize, crate::error::K2TreeError> {
+    use crate::error::K2TreeError as Error;
+    if stem_k < 2 {
+      return Err(Error::SmallStemKValue { stem_k: stem_k as u8 })
+    }
+    else if leaf_k < 2 {
+      return Err(Error::SmallLeafKValue { leaf_k: leaf_k as u8 })
+    }
+    let mut max_slayers = 2;
+    loop {
+      match stem_k.checked_pow(max_slayers as u32).and_then(|width| width.checked_mul(leaf_k)) {
+        Some(matrix_width) if matrix_width > max_coord => return Ok(max_slayers),
+        Some(_) => max_slayers += 1,
+        None => return Err(Error::OversizedMatrix {
+          stem_ks: vec![stem_k; max_slayers],
+          leaf_k,
+        }),
+      }
+    }
+  }
+  /* The k values of the stem-layers of a K2Tree with these stem-layers and `leaf_k` whose
+  matrix contains the coordinate `max_coord`, adding stem-layers with the k value of the
+  root above the existing ones until it fits */
+  fn stem_ks_to_fit(&self, leaf_k: usize, max_coord: usize) -> std::result::Result<Vec<usize>, crate::error::K2TreeError> {
+    let mut stem_ks = self.stem_ks();
+    loop {
+      match validate::checked_matrix_width(stem_ks.iter().copied(), leaf_k) {
+        Some(matrix_width) if matrix_width > max_coord => return Ok(stem_ks),
+        Some(_) => stem_ks.insert(0, self.stem_k),
+        None => return Err(crate::error::K2TreeError::OversizedMatrix { stem_ks, leaf_k }),
+      }
+    }
+  }
 }
 
 /* Block Utils */
 impl K2Tree {
+  /* The length of every stem, only meaningful when every stem-layer has the same k */
   fn stem_len(&self) -> usize {
     self.stem_k.pow(2)
   }
+  fn slayer_k(&self, layer: usize) -> usize {
+    match &self.stem_ks {
+      Some(stem_ks) => stem_ks[layer],
+      None => self.stem_k,
+    }
+  }
+  fn slayer_len(&self, layer: usize) -> usize {
+    self.slayer_k(layer).pow(2)
+  }
   fn leaf_len(&self) -> usize {
     self.leaf_k.pow(2)
   }
-  fn stem_start(&self, bit_pos: usize) -> usize {
-    (bit_pos / self.stem_len()) * self.stem_len()
+  /* The stem-layer containing the bit at `bit_pos` in the stems */
+  fn slayer_of(&self, bit_pos: usize) -> usize {
+    self.slayer_starts[..self.max_slayers].partition_point(|&start| start <= bit_pos) - 1
+  }
+  fn stem_start(&self, layer: usize, bit_pos: usize) -> usize {
+    let layer_start = self.layer_start(layer);
+    let stem_len = self.slayer_len(layer);
+    layer_start + ((bit_pos - layer_start) / stem_len) * stem_len
   }
+  fn to_subranges(&self, layer: usize, r: Range2D) -> std::result::Result<SubRanges, crate::error::SubRangesError> {
+    let stem_k = self.slayer_k(layer);
+    SubRanges::from_range(r, stem_k, stem_k)
+  }
+}
+
+/* The parts of a K2Tree needed to find the way from its root to any of its blocks,
+borrowed from either a K2Tree or a K2TreeView so that both are navigated the same way */
+#[derive(Debug)]
+struct Layout<'a, O = Local, T = usize>
+where O: BitOrder, T: BitStore {
+  stem_k: usize,
+  /* None if every stem-layer has the stem_k */
+  stem_ks: Option<&'a [usize]>,
+  leaf_k: usize,
+  max_slayers: usize,
+  stems: &'a BitSlice<O, T>,
+  leaves: &'a BitSlice<O, T>,
+  stem_ranks: &'a RankSelect,
+  slayer_starts: &'a [usize],
+}
+impl K2Tree {
+  fn layout(&self) -> Layout<'_> {
+    Layout {
+      stem_k: self.stem_k,
+      stem_ks: self.stem_ks.as_deref(),
+      leaf_k: self.leaf_k,
+      max_slayers: self.max_slayers,
+      stems: &self.stems,
+      leaves: &self.leaves,
+      stem_ranks: &self.stem_ranks,
+      slayer_starts: &self.slayer_starts,
+    }
+  }
+}
+impl<'a, O: BitOrder, T: BitStore> Layout<'a, O, T> {
+  fn slayer_k(&self, layer: usize) -> usize {
+    match self.stem_ks {
+      Some(stem_ks) => stem_ks[layer],
+      None => self.stem_k,
+    }
+  }
+  fn slayer_len(&self, layer: usize) -> usize {
+    self.slayer_k(layer).pow(2)
+  }
+  fn leaf_len(&self) -> usize {
+    self.leaf_k.pow(2)
+  }
+  fn matrix_width(&self) -> usize {
+    match self.stem_ks {
+      Some(stem_ks) => self.leaf_k * stem_ks.iter().product::<usize>(),
+      None => self.leaf_k * self.stem_k.pow(self.max_slayers as u32),
+    }
+  }
+  /* The state of the bit at (x, y), which must be inside the matrix */
+  fn get(&self, x: usize, y: usize) -> bool {
+    let mut stem_start = 0;
+    let [mut x, mut y] = [x, y];
+    let mut sub_width = self.matrix_width();
+    for layer in 0..self.max_slayers {
+      let stem_k = self.slayer_k(layer);
+      sub_width /= stem_k;
+      let bit_pos = stem_start + (y / sub_width) * stem_k + (x / sub_width);
+      if !self.stems[bit_pos] { return false }
+      x %= sub_width;
+      y %= sub_width;
+      if layer == self.max_slayers-1 {
+        return self.leaves[self.leaf_start(bit_pos) + y * self.leaf_k + x]
+      }
+      stem_start = self.child_start(layer, bit_pos);
+    }
+    unreachable!()
+  }
+  /* The start of the leaf pointed to by a 1 in the last stem-layer. Each 1 in the stems
+  points to the next block in level-order, so skip past the ones pointing to stems */
   fn leaf_start(&self, bit_pos: usize) -> usize {
-    (bit_pos / self.leaf_len()) * self.leaf_len()
+    (self.stem_ranks.rank(self.stems, bit_pos) - self.stems_after_root()) * self.leaf_len()
   }
-  fn to_subranges(&self, r: Range2D) -> std::result::Result<SubRanges, crate::error::SubRangesError> {
-    SubRanges::from_range(r, self.stem_k, self.stem_k)
+  /* The start of the stem in the next layer pointed to by the bit at `bit_pos`, or
+  where it would be if the bit were a 1 */
+  fn child_start(&self, layer: usize, bit_pos: usize) -> usize {
+    match self.stem_ks {
+      /* The nth 1 in the stems points to the (n+1)th stem,
+      as the first stem is the root */
+      None => (self.stem_ranks.rank(self.stems, bit_pos) + 1) * self.slayer_len(0),
+      /* Stems are different lengths in each layer, so count from the start of the next */
+      Some(_) => {
+        let nth_stem = self.stem_ranks.rank(self.stems, bit_pos)
+          - self.stem_ranks.rank(self.stems, self.slayer_starts[layer]);
+        self.slayer_starts[layer+1] + nth_stem * self.slayer_len(layer+1)
+      },
+    }
+  }
+  /* The number of stems after the root, which is the number of 1s in every stem-layer
+  but the last. The rest of the 1s point to leaves. */
+  fn stems_after_root(&self) -> usize {
+    match self.stem_ks {
+      None => self.stems.len() / self.slayer_len(0) - 1,
+      Some(_) => self.stem_ranks.rank(self.stems, self.slayer_starts[self.max_slayers-1]),
+    }
+  }
+  /* The coordinates of a bit in the leaves, found by walking up to the root */
+  fn get_coords(&self, leaf_bit_pos: usize) -> [usize; 2] {
+    let offset = leaf_bit_pos % self.leaf_len();
+    let [mut x, mut y] = [offset % self.leaf_k, offset / self.leaf_k];
+    let mut width = self.leaf_k;
+    let nth_leaf = leaf_bit_pos / self.leaf_len();
+    let mut bit_pos = self.stem_ranks.select(self.stems, self.stems_after_root() + nth_leaf).unwrap();
+    for layer in (0..self.max_slayers).rev() {
+      let stem_k = self.slayer_k(layer);
+      let layer_offset = bit_pos - self.slayer_starts[layer];
+      let child_pos = layer_offset % self.slayer_len(layer);
+      x += (child_pos % stem_k) * width;
+      y += (child_pos / stem_k) * width;
+      width *= stem_k;
+      if layer == 0 { break }
+      /* The nth stem in a layer is pointed to by the nth 1 in the layer above */
+      let nth_stem = layer_offset / self.slayer_len(layer);
+      let above = self.stem_ranks.rank(self.stems, self.slayer_starts[layer-1]);
+      bit_pos = self.stem_ranks.select(self.stems, above + nth_stem).unwrap();
+    }
+    [x, y]
   }
 }
 
-fn remove_block(bit_vec: &mut BitVec, block_start: usize, block_len: usize) -> std::result::Result<(), ()> {
+/* Blocks are aligned to `block_len` from `blocks_start`, where the run of blocks
+of that length begins */
+fn remove_block(bit_vec: &mut BitVec, blocks_start: usize, block_start: usize, block_len: usize) -> std::result::Result<(), ()> {
   if block_start > bit_vec.len()-block_len
-  || block_start % block_len != 0 {
+  || block_start < blocks_start
+  || (block_start - blocks_start) % block_len != 0 {
     Err(())
   }
   else {
@@ -152,9 +344,10 @@ fn remove_block(bit_vec: &mut BitVec, block_start: usize, block_len: usize) -> s
     Ok(())
   }
 }
-fn insert_block(bit_vec: &mut BitVec, block_start: usize, block_len: usize) -> std::result::Result<(), ()> {
+fn insert_block(bit_vec: &mut BitVec, blocks_start: usize, block_start: usize, block_len: usize) -> std::result::Result<(), ()> {
   if block_start > bit_vec.len()
-  || block_start % block_len != 0 {
+  || block_start < blocks_start
+  || (block_start - blocks_start) % block_len != 0 {
     Err(())
   }
   else {
@@ -162,9 +355,6 @@ fn insert_block(bit_vec: &mut BitVec, block_start: usize, block_len: usize) -> s
     Ok(())
   }
 }
-fn ones_in_range(bits: &BitVec, begin: usize, end: usize) -> usize {
-  bits[begin..end].into_iter().fold(0, |total, bit| total + *bit as usize)
-}
 fn all_zeroes(bits: &BitVec, begin: usize, end: usize) -> bool {
   bits[begin..end].into_iter().fold(true, |total, bit| total & !bit)
 }
@@ -177,7 +367,7 @@ fn one_positions(bits: impl Iterator<Item=bool>) -> Vec<usize> {
     else   { None })
   .collect()
 }
-fn one_positions_bv(bits: &BitVec) -> Vec<usize> {
+pub(crate) fn one_positions_bv(bits: &BitVec) -> Vec<usize> {
   bits.iter()
   .enumerate()
   .filter_map(
@@ -186,14 +376,6 @@ fn one_positions_bv(bits: &BitVec) -> Vec<usize> {
     else   { None })
   .collect()
 }
-fn one_positions_range(bits: &BitVec, begin: usize, end: usize) -> Vec<usize> {
-  bits[begin..end].into_iter().enumerate()
-  .filter_map(|(pos, bit)|
-    if *bit { Some(pos) }
-    else    { None }
-  ).collect()
-}
-
 /* Ranges */
 #[derive(Debug, Clone)]
 struct SubRanges {
@@ -278,6 +460,22 @@ impl Range2D {
     x >= self.min_x && x <= self.max_x
     && y >= self.min_y && y <= self.max_y
   }
+  fn contains_range(&self, other: &Range2D) -> bool {
+    self.min_x <= other.min_x && other.max_x <= self.max_x
+    && self.min_y <= other.min_y && other.max_y <= self.max_y
+  }
+  fn intersects(&self, other: &Range2D) -> bool {
+    self.min_x <= other.max_x && other.min_x <= self.max_x
+    && self.min_y <= other.max_y && other.min_y <= self.max_y
+  }
+  /* The range covered by the child at `child_pos` of a stem with a k value of `k`
+  covering this range */
+  fn child(&self, k: usize, child_pos: usize) -> Range2D {
+    let sub_width = self.width() / k;
+    let min_x = self.min_x + (child_pos % k) * sub_width;
+    let min_y = self.min_y + (child_pos / k) * sub_width;
+    Range2D::new(min_x, min_x + sub_width - 1, min_y, min_y + sub_width - 1)
+  }
 }
 impl PartialEq for Range2D {
   fn eq(&self, other: &Self) -> bool {
@@ -354,43 +552,43 @@ mod range_tests {
 impl K2Tree {
   fn test_tree(k: usize) -> Self {
     match k {
-      2 => K2Tree {
-        stem_k: 2,
-        leaf_k: 2,
-        max_slayers: 2,
-        stems:  bitvec![0,1,1,1, 1,1,0,1, 1,0,0,0, 1,0,0,0],
-        leaves: bitvec![0,1,1,0, 0,1,0,1, 1,1,0,0, 1,0,0,0, 0,1,1,0],
-      },
-      3 => K2Tree {
-        stem_k: 3,
-        leaf_k: 3,
-        max_slayers: 2,
-        stems:  bitvec![
+      2 => K2Tree::from_bits(
+        2,
+        2,
+        2,
+        bitvec![0,1,1,1, 1,1,0,1, 1,0,0,0, 1,0,0,0],
+        bitvec![0,1,1,0, 0,1,0,1, 1,1,0,0, 1,0,0,0, 0,1,1,0]
+      ),
+      3 => K2Tree::from_bits(
+        3,
+        3,
+        2,
+        bitvec![
           0,1,0,1,1,0,1,1,0, 1,1,0,0,0,0,0,0,0, 1,0,0,0,0,0,0,0,0,
           1,0,0,0,0,0,0,0,0, 1,0,0,0,0,0,0,0,0, 1,0,0,0,0,0,0,0,0
         ],
-        leaves: bitvec![
+        bitvec![
           0,1,0,1,0,0,0,0,1, 1,0,0,1,0,0,1,0,0, 1,0,0,0,0,0,0,0,0,
           0,1,0,1,0,0,0,0,0, 1,0,0,0,0,0,0,0,0, 0,1,0,1,0,0,0,0,0,
         ]
-      },
-      4 => K2Tree {
-        stem_k: 4,
-        leaf_k: 4,
-        max_slayers: 2,
-        stems: bitvec![
+      ),
+      4 => K2Tree::from_bits(
+        4,
+        4,
+        2,
+        bitvec![
           1,0,0,1,0,0,0,1,1,0,0,0,1,1,0,1, 1,0,0,0,0,0,0,0,0,1,0,0,0,0,0,0,
           0,0,0,0,0,0,1,0,0,0,0,0,0,0,0,0, 0,0,0,0,0,0,0,0,0,0,0,0,0,0,1,1,
           0,0,0,0,0,0,0,0,0,0,0,0,0,1,0,0, 0,0,0,0,0,0,0,0,0,0,0,0,0,1,1,1,
           1,0,1,0,0,0,0,0,0,0,0,0,0,0,0,0, 0,0,0,1,0,0,0,0,0,0,0,0,0,0,0,0,
         ],
-        leaves: bitvec![
+        bitvec![
           1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0, 0,0,0,0,1,1,0,0,0,0,0,0,0,0,0,0,
           0,0,0,0,0,0,0,0,0,0,0,0,1,0,0,0, 0,0,0,1,0,0,0,0,0,0,0,0,0,0,0,0,
           0,0,0,0,0,0,0,0,0,0,0,0,0,1,0,0, 0,0,0,1,0,0,0,0,0,0,0,0,0,0,0,0,
           0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1,
-        ],
-      },
+        ]
+      ),
       _ => K2Tree::with_k(2, 2).unwrap(),
     }
   }
@@ -0,0 +1,946 @@
+use {
+  std::{borrow::Cow, ops::{BitAnd, BitOr, BitOrAssign, BitXor, Sub}},
+  bitvec::prelude::{bitvec, BitSlice, BitVec},
+  crate::{
+    error::K2TreeError as Error,
+    tree::{K2Tree, K2TreeBuilder, RangeOnes, Range2D},
+  },
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl K2Tree {
+  /// Returns a K2Tree whose matrix has a 1 wherever either of the
+  /// input K2Trees' matrices do.
+  ///
+  /// Both K2Trees are walked together without decompressing either of them.
+  /// If their matrices are different sizes, the result is as large as the larger
+  /// one, or as wide as the wider and as tall as the taller if either has a fixed
+  /// width and height. If they have different k values, the result has those of `self`.
+  ///
+  /// Also available as the `|` and `|=` operators.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the K2Trees have different k values and the matrix of `other` is too
+  /// wide for a matrix_width with the k values of `self` to fit in a usize.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let a = K2Tree::from_coords(vec![(0, 0), (3, 5)], 2, 2)?;
+  ///   let b = K2Tree::from_coords(vec![(3, 5), (12, 1)], 2, 2)?;
+  ///   let union = a.union(&b);
+  ///   assert_eq!(16, union.matrix_width());
+  ///   assert_eq!(3, union.count_ones());
+  ///   assert_eq!(true, union.get(12, 1)?);
+  ///   assert_eq!(union, &a | &b);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn union(&self, other: &K2Tree) -> K2Tree {
+    self.combine(other, SetOp::Union)
+  }
+  /// Returns a K2Tree whose matrix has a 1 wherever both of the
+  /// input K2Trees' matrices do.
+  ///
+  /// Subtrees are skipped as soon as either K2Tree has no 1s in them.
+  /// Sizes and k values are handled the same way as in `union`.
+  ///
+  /// Also available as the `&` operator.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let a = K2Tree::from_coords(vec![(0, 0), (3, 5)], 2, 2)?;
+  ///   let b = K2Tree::from_coords(vec![(3, 5), (12, 1)], 2, 2)?;
+  ///   let intersection = a.intersection(&b);
+  ///   assert_eq!(1, intersection.count_ones());
+  ///   assert_eq!(true, intersection.get(3, 5)?);
+  ///   assert_eq!(intersection, &a & &b);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn intersection(&self, other: &K2Tree) -> K2Tree {
+    self.combine(other, SetOp::Intersection)
+  }
+  /// Returns a K2Tree whose matrix has a 1 wherever the matrix of `self`
+  /// does but that of `other` does not.
+  ///
+  /// Subtrees are skipped as soon as `self` has no 1s in them.
+  /// Sizes and k values are handled the same way as in `union`.
+  ///
+  /// Also available as the `-` operator.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let a = K2Tree::from_coords(vec![(0, 0), (3, 5)], 2, 2)?;
+  ///   let b = K2Tree::from_coords(vec![(3, 5), (12, 1)], 2, 2)?;
+  ///   let difference = a.difference(&b);
+  ///   assert_eq!(1, difference.count_ones());
+  ///   assert_eq!(true, difference.get(0, 0)?);
+  ///   assert_eq!(difference, &a - &b);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn difference(&self, other: &K2Tree) -> K2Tree {
+    self.combine(other, SetOp::Difference)
+  }
+  /// Returns a K2Tree whose matrix has a 1 wherever exactly one of the
+  /// input K2Trees' matrices does.
+  ///
+  /// Sizes and k values are handled the same way as in `union`.
+  ///
+  /// Also available as the `^` operator.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let a = K2Tree::from_coords(vec![(0, 0), (3, 5)], 2, 2)?;
+  ///   let b = K2Tree::from_coords(vec![(3, 5), (12, 1)], 2, 2)?;
+  ///   let symmetric_difference = a.symmetric_difference(&b);
+  ///   assert_eq!(2, symmetric_difference.count_ones());
+  ///   assert_eq!(false, symmetric_difference.get(3, 5)?);
+  ///   assert_eq!(symmetric_difference, &a ^ &b);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn symmetric_difference(&self, other: &K2Tree) -> K2Tree {
+    self.combine(other, SetOp::SymmetricDifference)
+  }
+  /// Returns a K2Tree representing the boolean product of this K2Tree's matrix
+  /// and that of `other`, so that there is a 1 at (x, y) wherever there is some z
+  /// with a 1 at (z, y) in this K2Tree's matrix and at (x, z) in that of `other`.
+  ///
+  /// For the adjacency matrix of a graph, multiplying it by itself finds the pairs of
+  /// nodes joined by a path of exactly two edges.
+  ///
+  /// The product is found block by block, skipping any pair of blocks where either
+  /// is all 0s. If the K2Trees have different k values, the result has those of `self`.
+  /// The result has the width of `other` and the height of `self`.
+  ///
+  /// Returns a DimensionMismatch error if the width of this K2Tree's matrix is not
+  /// the height of that of `other`, or an OversizedMatrix error if the K2Trees have
+  /// different k values and the matrix of `other` is too wide for a matrix_width
+  /// with the k values of `self` to fit in a usize.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   /* Edges 1 -> 2 and 2 -> 3, as a 1 at (to, from) */
+  ///   let tree = K2Tree::from_coords(vec![(2, 1), (3, 2)], 2, 2)?;
+  ///   let two_hops = tree.multiply(&tree)?;
+  ///   assert_eq!(1, two_hops.count_ones());
+  ///   assert_eq!(true, two_hops.get(3, 1)?);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn multiply(&self, other: &K2Tree) -> Result<K2Tree> {
+    if self.width() != other.height() {
+      return Err(Error::Read {
+        source: Box::new(Error::DimensionMismatch {
+          width: self.width(),
+          other_height: other.height(),
+        })
+      })
+    }
+    let (a, b) = self.aligned_with(other)?;
+    let mut builder = K2TreeBuilder::with_stem_ks(a.stem_ks(), a.leaf_k);
+    a.multiply_stem(&b, &mut builder, 0, &[(0, 0)], 0);
+    let mut product = builder.finish();
+    product.set_dimensions(other.width(), self.height());
+    Ok(product)
+  }
+  /// Returns a K2Tree representing this K2Tree's matrix raised to the power `n`,
+  /// using boolean matrix multiplication.
+  ///
+  /// For the adjacency matrix of a graph, this finds the pairs of nodes joined by a
+  /// path of exactly `n` edges. The 0th power is the identity matrix.
+  ///
+  /// Multiplication stops early if the product stops changing, as every
+  /// later power is then the same.
+  ///
+  /// Returns a NotSquare error if the matrix is not square, as only
+  /// square matrices have powers.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   /* Edges 0 -> 1, 1 -> 2 and 2 -> 3, as a 1 at (to, from) */
+  ///   let tree = K2Tree::from_coords(vec![(1, 0), (2, 1), (3, 2)], 2, 2)?;
+  ///   let cubed = tree.pow(3)?;
+  ///   assert_eq!(1, cubed.count_ones());
+  ///   assert_eq!(true, cubed.get(3, 0)?);
+  ///   assert!(tree.pow(4)?.is_empty());
+  ///   assert_eq!(8, tree.pow(0)?.count_ones());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn pow(&self, n: usize) -> Result<K2Tree> {
+    self.check_square()?;
+    if n == 0 {
+      let mut builder = K2TreeBuilder::with_stem_ks(self.stem_ks(), self.leaf_k);
+      for i in 0..self.width() {
+        builder.push(i, i)?;
+      }
+      let mut identity = builder.finish();
+      identity.set_dimensions(self.width(), self.height());
+      return Ok(identity)
+    }
+    let mut power = self.clone();
+    for _ in 1..n {
+      let next = power.multiply(self)?;
+      if next == power { break }
+      power = next;
+    }
+    Ok(power)
+  }
+  /// Returns a K2Tree representing the transitive closure of this K2Tree's matrix,
+  /// which is the union of all of its powers from the 1st upwards.
+  ///
+  /// For the adjacency matrix of a graph, this finds every pair of nodes joined by
+  /// a path of one or more edges.
+  ///
+  /// The closure is found by repeatedly adding its own square to it until it stops
+  /// changing, which takes a number of steps logarithmic in the length of the longest path.
+  ///
+  /// Returns a NotSquare error if the matrix is not square.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   /* Edges 0 -> 1, 1 -> 2 and 2 -> 3, as a 1 at (to, from) */
+  ///   let tree = K2Tree::from_coords(vec![(1, 0), (2, 1), (3, 2)], 2, 2)?;
+  ///   let closure = tree.transitive_closure()?;
+  ///   assert_eq!(6, closure.count_ones());
+  ///   assert_eq!(true, closure.get(3, 0)?);
+  ///   assert_eq!(false, closure.get(0, 0)?);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn transitive_closure(&self) -> Result<K2Tree> {
+    self.check_square()?;
+    let mut closure = self.clone();
+    loop {
+      let next = closure.union(&closure.multiply(&closure)?);
+      if next == closure { return Ok(closure) }
+      closure = next;
+    }
+  }
+  /// Returns the x-coordinates of every 1 in the rows of the matrix at the
+  /// y-coordinates in `rows`, in ascending order and without duplicates.
+  ///
+  /// This is the boolean product of the vector with a 1 at each position in `rows`
+  /// and the matrix: for the adjacency matrix of a graph, it is every node
+  /// reachable in a single step from any of the nodes in `rows`.
+  ///
+  /// Only the parts of the K2Tree that overlap at least one of the rows are visited.
+  /// Rows outside of the matrix are ignored.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let tree = K2Tree::from_coords(vec![(2, 1), (3, 2), (5, 1), (3, 4)], 2, 2)?;
+  ///   assert_eq!(vec![2, 3, 5], tree.mul_vector(&[1, 2]));
+  ///   assert_eq!(vec![3], tree.mul_vector(&[2, 4, 7]));
+  ///   assert!(tree.mul_vector(&[0]).is_empty());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn mul_vector(&self, rows: &[usize]) -> Vec<usize> {
+    let mut rows = rows.to_vec();
+    rows.sort_unstable();
+    rows.dedup();
+    self.mul_rows(&rows)
+  }
+  /// Returns the boolean product of the vector `rows` and the matrix, as a BitVec
+  /// of length `width` with a 1 at the x-coordinate of every 1 in the rows of
+  /// the matrix whose y-coordinates are 1 in `rows`.
+  ///
+  /// Bits of `rows` past the end of the matrix are ignored.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   use bitvec::prelude::bitvec;
+  ///   let tree = K2Tree::from_coords(vec![(2, 1), (3, 2), (5, 1), (3, 4)], 2, 2)?;
+  ///   let columns = tree.mul_bitvec(&bitvec![0, 1, 1]);
+  ///   assert_eq!(bitvec![0, 0, 1, 1, 0, 1, 0, 0], columns);
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn mul_bitvec(&self, rows: &BitSlice) -> BitVec {
+    let rows: Vec<usize> = rows.iter()
+      .take(self.height())
+      .enumerate()
+      .filter_map(|(y, &bit)| if bit { Some(y) } else { None })
+      .collect();
+    let mut columns = bitvec![0; self.width()];
+    for x in self.mul_rows(&rows) {
+      columns.set(x, true);
+    }
+    columns
+  }
+  /// Returns a K2Tree representing the transpose of this K2Tree's matrix,
+  /// so that there is a 1 at (y, x) wherever this K2Tree's matrix has a 1 at (x, y).
+  ///
+  /// The transposed K2Tree is built directly from this one by visiting the children
+  /// of every stem and leaf in transposed order, without decompressing it.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::K2Tree;
+  ///   let tree = K2Tree::from_coords(vec![(0, 5), (12, 3)], 2, 2)?;
+  ///   let transposed = tree.transpose();
+  ///   assert_eq!(true, transposed.get(5, 0)?);
+  ///   assert_eq!(true, transposed.get(3, 12)?);
+  ///   assert_eq!(2, transposed.count_ones());
+  ///   assert_eq!(tree, transposed.transpose());
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn transpose(&self) -> K2Tree {
+    let mut builder = K2TreeBuilder::with_stem_ks(self.stem_ks(), self.leaf_k);
+    self.transpose_stem(&mut builder, 0, 0, 0);
+    let mut transposed = builder.finish();
+    transposed.set_dimensions(self.height(), self.width());
+    transposed
+  }
+}
+
+/* Private */
+impl K2Tree {
+  /* Only a square matrix can be multiplied by itself */
+  pub(crate) fn check_square(&self) -> Result<()> {
+    if self.width() != self.height() {
+      return Err(Error::Read {
+        source: Box::new(Error::NotSquare {
+          width: self.width(),
+          height: self.height(),
+        })
+      })
+    }
+    Ok(())
+  }
+  /* Walks both trees together, building the result of `op` on them as it goes */
+  fn combine(&self, other: &K2Tree, op: SetOp) -> K2Tree {
+    let (a, b) = self.aligned_with(other)
+      .expect("matrix of other is too wide for the k values of self");
+    let mut walk = Walk {
+      a: &a,
+      b: &b,
+      op,
+      builder: K2TreeBuilder::with_stem_ks(a.stem_ks(), a.leaf_k),
+    };
+    walk.stem(0, Some(0), Some(0), 0);
+    let mut result = walk.builder.finish();
+    /* Without a fixed width and height the result can keep its own matrix_width */
+    if self.dimensions.is_some() || other.dimensions.is_some() {
+      result.set_dimensions(
+        std::cmp::max(self.width(), other.width()),
+        std::cmp::max(self.height(), other.height())
+      );
+    }
+    result
+  }
+  /* Pushes the 1s of a block of the product to `builder`, where the block is the
+  sum of the products of the pairs of stems in `pairs`, the first of each from
+  `self` and the second from `other` */
+  fn multiply_stem(&self, other: &K2Tree, builder: &mut K2TreeBuilder, layer: usize, pairs: &[(usize, usize)], key: u128) {
+    let stem_k = self.slayer_k(layer);
+    let stem_len = self.slayer_len(layer);
+    let last_layer = layer == self.max_slayers-1;
+    for child_pos in 0..stem_len {
+      /* The child at (x, y) of the product is the sum of the products of the
+      children at (z, y) in `self` and (x, z) in `other` for every z */
+      let [x, y] = [child_pos % stem_k, child_pos / stem_k];
+      let mut child_pairs = Vec::new();
+      for &(a_start, b_start) in pairs.iter() {
+        for z in 0..stem_k {
+          let a_pos = a_start + y * stem_k + z;
+          let b_pos = b_start + z * stem_k + x;
+          if !self.stems[a_pos] || !other.stems[b_pos] { continue }
+          if last_layer {
+            child_pairs.push((
+              self.stem_to_leaf_start(a_pos).unwrap(),
+              other.stem_to_leaf_start(b_pos).unwrap(),
+            ));
+          }
+          else {
+            child_pairs.push((
+              self.child_stem(layer, a_start, y * stem_k + z).unwrap(),
+              other.child_stem(layer, b_start, z * stem_k + x).unwrap(),
+            ));
+          }
+        }
+      }
+      if child_pairs.is_empty() { continue }
+      let child_key = key * stem_len as u128 + child_pos as u128;
+      if last_layer { self.multiply_leaf(other, builder, &child_pairs, child_key); }
+      else { self.multiply_stem(other, builder, layer+1, &child_pairs, child_key); }
+    }
+  }
+  fn multiply_leaf(&self, other: &K2Tree, builder: &mut K2TreeBuilder, pairs: &[(usize, usize)], key: u128) {
+    let leaf_k = self.leaf_k;
+    let leaf_len = self.leaf_len();
+    for offset in 0..leaf_len {
+      let [x, y] = [offset % leaf_k, offset / leaf_k];
+      let bit = pairs.iter().any(|&(a_start, b_start)| {
+        (0..leaf_k).any(|z| {
+          self.leaves[a_start + y * leaf_k + z] && other.leaves[b_start + z * leaf_k + x]
+        })
+      });
+      if bit {
+        builder.push_key(key * leaf_len as u128 + offset as u128);
+      }
+    }
+  }
+  /* Pushes the 1s below a stem to `builder` in the order of the transposed tree,
+  where the child at (i, j) in each block is moved to (j, i) */
+  fn transpose_stem(&self, builder: &mut K2TreeBuilder, layer: usize, stem_start: usize, key: u128) {
+    let stem_k = self.slayer_k(layer);
+    let stem_len = self.slayer_len(layer);
+    for child_pos in 0..stem_len {
+      let original_pos = (child_pos % stem_k) * stem_k + child_pos / stem_k;
+      if !self.stems[stem_start+original_pos] { continue }
+      let child_key = key * stem_len as u128 + child_pos as u128;
+      if layer == self.max_slayers-1 {
+        let leaf_start = self.stem_to_leaf_start(stem_start+original_pos).unwrap();
+        self.transpose_leaf(builder, leaf_start, child_key);
+      }
+      else {
+        let child_start = self.child_stem(layer, stem_start, original_pos).unwrap();
+        self.transpose_stem(builder, layer+1, child_start, child_key);
+      }
+    }
+  }
+  fn transpose_leaf(&self, builder: &mut K2TreeBuilder, leaf_start: usize, key: u128) {
+    let leaf_k = self.leaf_k;
+    let leaf_len = self.leaf_len();
+    for offset in 0..leaf_len {
+      let original_offset = (offset % leaf_k) * leaf_k + offset / leaf_k;
+      if self.leaves[leaf_start+original_offset] {
+        builder.push_key(key * leaf_len as u128 + offset as u128);
+      }
+    }
+  }
+  /* The columns with a 1 in any of the sorted and deduplicated `rows`, in ascending
+  order and without duplicates */
+  fn mul_rows(&self, rows: &[usize]) -> Vec<usize> {
+    let matrix_width = self.matrix_width();
+    let mut columns = Vec::new();
+    let first_outside = rows.partition_point(|&y| y < matrix_width);
+    if !self.is_empty() && first_outside > 0 {
+      let range = Range2D::new(0, matrix_width-1, 0, matrix_width-1);
+      self.mul_rows_stem(0, 0, range, &rows[..first_outside], &mut columns);
+    }
+    /* Children are visited row by row, so columns arrive out of order */
+    columns.sort_unstable();
+    columns.dedup();
+    columns
+  }
+  fn mul_rows_stem(&self, layer: usize, stem_start: usize, range: Range2D, rows: &[usize], columns: &mut Vec<usize>) {
+    let subranges = self.to_subranges(layer, range).unwrap();
+    for child_pos in 0..self.slayer_len(layer) {
+      if !self.stems[stem_start+child_pos] { continue }
+      /* Only descend with the rows that pass through the child */
+      let child_range = subranges[child_pos];
+      let first = rows.partition_point(|&y| y < child_range.min_y);
+      let end = rows.partition_point(|&y| y <= child_range.max_y);
+      if first == end { continue }
+      if layer == self.max_slayers-1 {
+        let leaf_start = self.stem_to_leaf_start(stem_start+child_pos).unwrap();
+        for &y in rows[first..end].iter() {
+          let row_start = leaf_start + (y - child_range.min_y) * self.leaf_k;
+          for x in 0..self.leaf_k {
+            if self.leaves[row_start+x] { columns.push(child_range.min_x + x); }
+          }
+        }
+      }
+      else {
+        let child_start = self.child_stem(layer, stem_start, child_pos).unwrap();
+        self.mul_rows_stem(layer+1, child_start, child_range, &rows[first..end], columns);
+      }
+    }
+  }
+  /* `self` and `other` with the same k values and matrix_width, growing
+  whichever is smaller */
+  fn aligned_with<'a>(&'a self, other: &'a K2Tree) -> Result<(Cow<'a, K2Tree>, Cow<'a, K2Tree>)> {
+    let mut a = Cow::Borrowed(self);
+    let mut b = self.with_same_k(other)?;
+    while a.max_slayers < b.max_slayers { a.to_mut().grow(); }
+    while b.max_slayers < a.max_slayers { b.to_mut().grow(); }
+    Ok((a, b))
+  }
+  /* `other`, rebuilt with the k values of `self` if they differ.
+  Stem-layers only ever grow above the root with its k value, so the k values are
+  the same if the extra stem-layers of the taller tree all have the other's root k */
+  fn with_same_k<'a>(&self, other: &'a K2Tree) -> Result<Cow<'a, K2Tree>> {
+    let [self_ks, other_ks] = [self.stem_ks(), other.stem_ks()];
+    let (short, tall) = if self_ks.len() <= other_ks.len() { (&self_ks, &other_ks) }
+      else { (&other_ks, &self_ks) };
+    let extra = tall.len() - short.len();
+    if self.leaf_k == other.leaf_k
+    && tall[extra..] == short[..]
+    && tall[..extra].iter().all(|&stem_k| stem_k == short[0]) {
+      return Ok(Cow::Borrowed(other))
+    }
+    let stem_ks = self.stem_ks_to_fit(self.leaf_k, other.matrix_width()-1)?;
+    let mut builder = K2TreeBuilder::with_stem_ks(stem_ks, self.leaf_k);
+    let range_max = other.matrix_width()-1;
+    let mut keys: Vec<u128> = RangeOnes::new(other, 0, range_max, 0, range_max)
+      .map(|(x, y)| builder.z_order(x, y))
+      .collect();
+    keys.sort_unstable();
+    for key in keys.into_iter() {
+      builder.push_key(key);
+    }
+    Ok(Cow::Owned(builder.finish()))
+  }
+}
+
+/* The operations that can be performed by walking two K2Trees together */
+#[derive(Debug, Clone, Copy)]
+enum SetOp {
+  Union,
+  Intersection,
+  Difference,
+  SymmetricDifference,
+}
+impl SetOp {
+  /* Whether the result can have any 1s in a subtree, given whether each tree does */
+  fn visit(self, a: bool, b: bool) -> bool {
+    match self {
+      SetOp::Union => a || b,
+      SetOp::Intersection => a && b,
+      SetOp::Difference => a,
+      SetOp::SymmetricDifference => a || b,
+    }
+  }
+  /* The state of a bit in the result, given its state in each tree */
+  fn apply(self, a: bool, b: bool) -> bool {
+    match self {
+      SetOp::Union => a || b,
+      SetOp::Intersection => a && b,
+      SetOp::Difference => a && !b,
+      SetOp::SymmetricDifference => a != b,
+    }
+  }
+}
+
+/* The state of a walk over two K2Trees with the same k values and matrix_width.
+Subtrees are visited depth-first, so the 1s of the result are found in the order
+a K2TreeBuilder needs them. */
+struct Walk<'a> {
+  a: &'a K2Tree,
+  b: &'a K2Tree,
+  op: SetOp,
+  builder: K2TreeBuilder,
+}
+impl<'a> Walk<'a> {
+  /* Visits the stems starting at `a_start` and `b_start` in each tree, either of
+  which is None if that tree has no 1s there */
+  fn stem(&mut self, layer: usize, a_start: Option<usize>, b_start: Option<usize>, key: u128) {
+    let stem_len = self.a.slayer_len(layer);
+    let last_layer = layer == self.a.max_slayers-1;
+    for child_pos in 0..stem_len {
+      let child = |tree: &K2Tree, stem_start: Option<usize>| {
+        let stem_start = stem_start?;
+        if last_layer { tree.stem_to_leaf_start(stem_start+child_pos).ok() }
+        else { tree.child_stem(layer, stem_start, child_pos).ok() }
+      };
+      let a_child = child(self.a, a_start);
+      let b_child = child(self.b, b_start);
+      if !self.op.visit(a_child.is_some(), b_child.is_some()) { continue }
+      let child_key = key * stem_len as u128 + child_pos as u128;
+      if last_layer { self.leaf(a_child, b_child, child_key); }
+      else { self.stem(layer+1, a_child, b_child, child_key); }
+    }
+  }
+  fn leaf(&mut self, a_start: Option<usize>, b_start: Option<usize>, key: u128) {
+    let leaf_len = self.a.leaf_len();
+    for offset in 0..leaf_len {
+      let a_bit = bit(&self.a.leaves, a_start, offset);
+      let b_bit = bit(&self.b.leaves, b_start, offset);
+      if self.op.apply(a_bit, b_bit) {
+        self.builder.push_key(key * leaf_len as u128 + offset as u128);
+      }
+    }
+  }
+}
+fn bit(bits: &BitVec, block_start: Option<usize>, offset: usize) -> bool {
+  match block_start {
+    Some(block_start) => bits[block_start+offset],
+    None => false,
+  }
+}
+
+/* Traits */
+impl BitOr<&K2Tree> for &K2Tree {
+  type Output = K2Tree;
+  fn bitor(self, rhs: &K2Tree) -> K2Tree {
+    self.union(rhs)
+  }
+}
+impl BitOr for K2Tree {
+  type Output = K2Tree;
+  fn bitor(self, rhs: K2Tree) -> K2Tree {
+    self.union(&rhs)
+  }
+}
+impl BitOrAssign<&K2Tree> for K2Tree {
+  fn bitor_assign(&mut self, rhs: &K2Tree) {
+    *self = self.union(rhs);
+  }
+}
+impl BitOrAssign for K2Tree {
+  fn bitor_assign(&mut self, rhs: K2Tree) {
+    *self = self.union(&rhs);
+  }
+}
+impl BitAnd<&K2Tree> for &K2Tree {
+  type Output = K2Tree;
+  fn bitand(self, rhs: &K2Tree) -> K2Tree {
+    self.intersection(rhs)
+  }
+}
+impl BitAnd for K2Tree {
+  type Output = K2Tree;
+  fn bitand(self, rhs: K2Tree) -> K2Tree {
+    self.intersection(&rhs)
+  }
+}
+impl Sub<&K2Tree> for &K2Tree {
+  type Output = K2Tree;
+  fn sub(self, rhs: &K2Tree) -> K2Tree {
+    self.difference(rhs)
+  }
+}
+impl Sub for K2Tree {
+  type Output = K2Tree;
+  fn sub(self, rhs: K2Tree) -> K2Tree {
+    self.difference(&rhs)
+  }
+}
+impl BitXor<&K2Tree> for &K2Tree {
+  type Output = K2Tree;
+  fn bitxor(self, rhs: &K2Tree) -> K2Tree {
+    self.symmetric_difference(rhs)
+  }
+}
+impl BitXor for K2Tree {
+  type Output = K2Tree;
+  fn bitxor(self, rhs: K2Tree) -> K2Tree {
+    self.symmetric_difference(&rhs)
+  }
+}
+
+#[cfg(test)]
+mod api {
+  use super::*;
+  use crate::tree::one_positions_bv;
+  fn random_coords(n: usize, width: usize) -> Vec<(usize, usize)> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    (0..n).map(|_| (rng.gen_range(0, width), rng.gen_range(0, width))).collect()
+  }
+  /* A K2Tree of a specific matrix_width, with a 1 at each of the coordinates */
+  fn tree_of(mut coords: Vec<(usize, usize)>, stem_k: usize, leaf_k: usize, width: usize) -> Result<K2Tree> {
+    let mut builder = K2TreeBuilder::new(stem_k, leaf_k, width)?;
+    coords.sort_by_key(|&(x, y)| builder.z_order(x, y));
+    for (x, y) in coords {
+      builder.push(x, y)?;
+    }
+    Ok(builder.finish())
+  }
+  #[test]
+  fn union() -> Result<()> {
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let a_coords = random_coords(100, 60);
+        let b_coords = random_coords(100, 60);
+        let a = tree_of(a_coords.clone(), stem_k, leaf_k, 60)?;
+        let b = tree_of(b_coords.clone(), stem_k, leaf_k, 60)?;
+        let expected = tree_of([a_coords, b_coords].concat(), stem_k, leaf_k, 60)?;
+        assert_eq!(expected, a.union(&b));
+        assert_eq!(expected, b.union(&a));
+        assert_eq!(a, a.union(&a));
+        assert_eq!(a, a.union(&K2Tree::with_k(stem_k, leaf_k)?));
+      }
+    }
+    Ok(())
+  }
+  #[test]
+  fn union_different_sizes() -> Result<()> {
+    let a_coords = random_coords(50, 8);
+    let b_coords = random_coords(50, 40);
+    let a = tree_of(a_coords.clone(), 2, 2, 8)?;
+    let b = tree_of(b_coords.clone(), 2, 2, 40)?;
+    let expected = tree_of([a_coords.clone(), b_coords.clone()].concat(), 2, 2, 64)?;
+    assert_eq!(expected, a.union(&b));
+    assert_eq!(expected, b.union(&a));
+    /* The k values of the left-hand side are kept */
+    let c = tree_of(b_coords.clone(), 3, 4, 40)?;
+    assert_eq!(108, c.matrix_width());
+    let expected = tree_of([a_coords.clone(), b_coords.clone()].concat(), 2, 2, 108)?;
+    assert_eq!(expected, a.union(&c));
+    let expected = tree_of([a_coords, b_coords].concat(), 3, 4, 40)?;
+    assert_eq!(expected, c.union(&a));
+    Ok(())
+  }
+  #[test]
+  fn bitor() -> Result<()> {
+    let a = K2Tree::test_tree(2);
+    let b = K2Tree::from_coords(vec![(0, 0), (5, 0), (7, 7)], 2, 2)?;
+    let expected = a.union(&b);
+    assert_eq!(expected, &a | &b);
+    assert_eq!(expected, a.clone() | b.clone());
+    let mut c = a.clone();
+    c |= &b;
+    assert_eq!(expected, c);
+    let mut c = a;
+    c |= b;
+    assert_eq!(expected, c);
+    Ok(())
+  }
+  #[test]
+  fn intersection_and_differences() -> Result<()> {
+    use std::collections::HashSet;
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let a_coords: HashSet<(usize, usize)> = random_coords(150, 60).into_iter().collect();
+        let b_coords: HashSet<(usize, usize)> = random_coords(150, 60).into_iter().collect();
+        let a = tree_of(a_coords.iter().cloned().collect(), stem_k, leaf_k, 60)?;
+        let b = tree_of(b_coords.iter().cloned().collect(), stem_k, leaf_k, 60)?;
+        let expected = |coords: HashSet<&(usize, usize)>| {
+          tree_of(coords.into_iter().cloned().collect(), stem_k, leaf_k, 60)
+        };
+        assert_eq!(expected(a_coords.intersection(&b_coords).collect())?, a.intersection(&b));
+        assert_eq!(expected(a_coords.difference(&b_coords).collect())?, a.difference(&b));
+        assert_eq!(expected(b_coords.difference(&a_coords).collect())?, b.difference(&a));
+        assert_eq!(
+          expected(a_coords.symmetric_difference(&b_coords).collect())?,
+          a.symmetric_difference(&b)
+        );
+        let empty = K2Tree::with_k(stem_k, leaf_k)?;
+        assert!(a.intersection(&empty).is_empty());
+        assert!(a.difference(&a).is_empty());
+        assert!(a.symmetric_difference(&a).is_empty());
+        assert_eq!(a, a.difference(&empty));
+      }
+    }
+    Ok(())
+  }
+  #[test]
+  fn intersection_different_sizes() -> Result<()> {
+    let a = K2Tree::from_coords(vec![(0, 0), (3, 5), (7, 7)], 2, 2)?;
+    let b = K2Tree::from_coords(vec![(3, 5), (7, 7), (40, 3)], 3, 2)?;
+    let intersection = a.intersection(&b);
+    assert_eq!(2, intersection.stem_k);
+    assert_eq!(64, intersection.matrix_width());
+    assert_eq!(K2Tree::from_coords(vec![(3, 5), (7, 7), (63, 63)], 2, 2)?.intersection(&a), intersection);
+    assert_eq!(vec![(3, 5), (7, 7)], intersection.ones_in_range(0..=63, 0..=63)?);
+    let difference = b.difference(&a);
+    assert_eq!(3, difference.stem_k);
+    assert_eq!(vec![(40, 3)], difference.ones_in_range(0..=53, 0..=53)?);
+    Ok(())
+  }
+  #[test]
+  fn operators() -> Result<()> {
+    let a = K2Tree::test_tree(2);
+    let b = K2Tree::from_coords(vec![(0, 0), (5, 0), (7, 7)], 2, 2)?;
+    assert_eq!(a.intersection(&b), &a & &b);
+    assert_eq!(a.intersection(&b), a.clone() & b.clone());
+    assert_eq!(a.difference(&b), &a - &b);
+    assert_eq!(a.difference(&b), a.clone() - b.clone());
+    assert_eq!(a.symmetric_difference(&b), &a ^ &b);
+    assert_eq!(a.symmetric_difference(&b), a ^ b);
+    Ok(())
+  }
+  #[test]
+  fn transpose() -> Result<()> {
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let coords = random_coords(150, 60);
+        let tree = tree_of(coords.clone(), stem_k, leaf_k, 60)?;
+        let transposed_coords = coords.into_iter().map(|(x, y)| (y, x)).collect();
+        let transposed = tree.transpose();
+        assert_eq!(tree_of(transposed_coords, stem_k, leaf_k, 60)?, transposed);
+        assert_eq!(tree, transposed.transpose());
+      }
+    }
+    let empty = K2Tree::with_k(3, 2)?;
+    assert_eq!(empty, empty.transpose());
+    Ok(())
+  }
+  #[test]
+  fn multiply() -> Result<()> {
+    use crate::matrix::BitMatrix;
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let a = tree_of(random_coords(150, 60), stem_k, leaf_k, 60)?;
+        let b = tree_of(random_coords(150, 60), stem_k, leaf_k, 60)?;
+        let [a_matrix, b_matrix] = [a.to_matrix()?, b.to_matrix()?];
+        let width = a.matrix_width();
+        let mut expected = BitMatrix::with_dimensions(width, width);
+        for y in 0..width {
+          for x in 0..width {
+            for z in 0..width {
+              if a_matrix.get(z, y)? && b_matrix.get(x, z)? {
+                expected.set(x, y, true)?;
+                break
+              }
+            }
+          }
+        }
+        assert_eq!(expected, a.multiply(&b)?.to_matrix()?);
+        let empty = K2Tree::with_k(stem_k, leaf_k)?;
+        if empty.matrix_width() == width {
+          assert!(a.multiply(&empty)?.is_empty());
+        }
+      }
+    }
+    Ok(())
+  }
+  #[test]
+  fn multiply_identity() -> Result<()> {
+    let tree = tree_of(random_coords(100, 27), 3, 3, 27)?;
+    let identity = K2Tree::from_coords((0..27).map(|i| (i, i)), 3, 3)?;
+    assert_eq!(tree, tree.multiply(&identity)?);
+    assert_eq!(tree, identity.multiply(&tree)?);
+    /* Different k values are fine as long as the widths match */
+    let tree = tree_of(random_coords(100, 16), 2, 2, 16)?;
+    let identity = K2Tree::from_coords((0..16).map(|i| (i, i)), 2, 4)?;
+    assert_eq!(16, identity.matrix_width());
+    assert_eq!(tree, tree.multiply(&identity)?);
+    let tree = tree_of(random_coords(100, 8), 2, 2, 8)?;
+    assert_eq!(
+      Err(Error::Read {
+        source: Box::new(Error::DimensionMismatch {
+          width: 8,
+          other_height: 16,
+        })
+      }),
+      tree.multiply(&identity)
+    );
+    /* A matrix 3^40 wide only fits in a usize with k values of 3 */
+    let narrow = K2Tree::with_dimensions(2, 2, 8, 8)?;
+    let wide = K2Tree::with_dimensions(3, 3, 3usize.pow(40), 8)?;
+    assert_eq!(
+      Err(Error::OversizedMatrix { stem_ks: vec![2; 63], leaf_k: 2 }),
+      narrow.multiply(&wide)
+    );
+    Ok(())
+  }
+  #[test]
+  fn mul_vector() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let tree = tree_of(random_coords(300, 60), stem_k, leaf_k, 60)?;
+        let width = tree.matrix_width();
+        let rows: Vec<usize> = (0..10).map(|_| rng.gen_range(0, width + 5)).collect();
+        let mut expected = Vec::new();
+        for &y in rows.iter().filter(|&&y| y < width) {
+          expected.extend(tree.successors(y)?);
+        }
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(expected, tree.mul_vector(&rows));
+        let rows_bv: BitVec = (0..width + 5).map(|y| rows.contains(&y)).collect();
+        let columns = tree.mul_bitvec(&rows_bv);
+        assert_eq!(width, columns.len());
+        assert_eq!(expected, one_positions_bv(&columns));
+      }
+    }
+    let empty = K2Tree::new();
+    assert!(empty.mul_vector(&[0, 1, 2]).is_empty());
+    assert_eq!(bitvec![0; 8], empty.mul_bitvec(&bitvec![1; 8]));
+    assert!(K2Tree::test_tree(2).mul_vector(&[]).is_empty());
+    Ok(())
+  }
+  #[test]
+  fn pow() -> Result<()> {
+    for stem_k in 2..4 {
+      for leaf_k in 2..4 {
+        let tree = tree_of(random_coords(60, 40), stem_k, leaf_k, 40)?;
+        let mut expected = tree.pow(0)?;
+        assert_eq!(tree.matrix_width(), expected.count_ones());
+        for n in 1..6 {
+          expected = expected.multiply(&tree)?;
+          assert_eq!(expected, tree.pow(n)?);
+        }
+      }
+    }
+    /* Powers of a nilpotent matrix stop changing once they reach 0 */
+    let path = K2Tree::from_coords((0..15).map(|i| (i+1, i)), 2, 2)?;
+    assert_eq!(1, path.pow(15)?.count_ones());
+    assert!(path.pow(16)?.is_empty());
+    assert!(path.pow(1000)?.is_empty());
+    /* Only a square matrix has powers */
+    let wide = K2Tree::with_dimensions(2, 2, 8, 5)?;
+    assert_eq!(
+      Err(Error::Read {
+        source: Box::new(Error::NotSquare { width: 8, height: 5 })
+      }),
+      wide.pow(2)
+    );
+    Ok(())
+  }
+  #[test]
+  fn transitive_closure() -> Result<()> {
+    for stem_k in 2..4 {
+      for leaf_k in 2..4 {
+        let tree = tree_of(random_coords(40, 40), stem_k, leaf_k, 40)?;
+        let width = tree.matrix_width();
+        /* Everything reachable in one or more steps from each row */
+        let mut expected = Vec::new();
+        for y in 0..width {
+          let mut reached = vec![false; width];
+          let mut frontier = tree.successors(y)?;
+          while let Some(x) = frontier.pop() {
+            if reached[x] { continue }
+            reached[x] = true;
+            frontier.extend(tree.successors(x)?);
+          }
+          expected.extend((0..width).filter(|&x| reached[x]).map(|x| (x, y)));
+        }
+        let closure = tree.transitive_closure()?;
+        assert_eq!(tree_of(expected, stem_k, leaf_k, 40)?, closure);
+        assert_eq!(closure, closure.transitive_closure()?);
+      }
+    }
+    let path = K2Tree::from_coords((0..15).map(|i| (i+1, i)), 2, 2)?;
+    assert_eq!(16 * 15 / 2, path.transitive_closure()?.count_ones());
+    let tall = K2Tree::with_dimensions(2, 2, 5, 8)?;
+    assert_eq!(
+      Err(Error::Read {
+        source: Box::new(Error::NotSquare { width: 5, height: 8 })
+      }),
+      tall.transitive_closure()
+    );
+    Ok(())
+  }
+  #[test]
+  fn stem_ks() -> Result<()> {
+    let hybrid_of = |coords: &[(usize, usize)]| -> Result<K2Tree> {
+      let mut tree = K2Tree::with_stem_ks(&[4, 3, 2], 2)?;
+      tree.set_many(coords.iter().map(|&(x, y)| (x, y, true)).collect::<Vec<_>>())?;
+      Ok(tree)
+    };
+    let [a_coords, b_coords] = [random_coords(150, 48), random_coords(150, 48)];
+    let [a, b] = [hybrid_of(&a_coords)?, hybrid_of(&b_coords)?];
+    assert_eq!(hybrid_of(&[a_coords.clone(), b_coords.clone()].concat())?, a.union(&b));
+    let transposed_coords: Vec<(usize, usize)> = a_coords.iter().map(|&(x, y)| (y, x)).collect();
+    assert_eq!(hybrid_of(&transposed_coords)?, a.transpose());
+    let product = a.multiply(&b)?;
+    assert_eq!(vec![4, 3, 2], product.stem_ks());
+    let uniform = tree_of(a_coords.clone(), 2, 2, 48)?;
+    assert_eq!(uniform.multiply(&tree_of(b_coords.clone(), 2, 2, 48)?)?.count_ones(), product.count_ones());
+    /* A uniform K2Tree is rebuilt with the k values of the hybrid one, which grows
+    to fit its wider matrix */
+    let union = a.union(&uniform);
+    assert_eq!(vec![4, 4, 3, 2], union.stem_ks());
+    let mut grown_a = a.clone();
+    grown_a.grow();
+    assert_eq!(grown_a, union);
+    /* Growing keeps the two compatible without a rebuild */
+    let mut grown = b.clone();
+    grown.grow();
+    assert_eq!(vec![4, 4, 3, 2], a.union(&grown).stem_ks());
+    assert_eq!(a.union(&b).count_ones(), a.union(&grown).count_ones());
+    Ok(())
+  }
+}
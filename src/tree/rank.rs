@@ -0,0 +1,188 @@
+use bitvec::prelude::{BitOrder, BitSlice, BitStore};
+
+/// Number of bits covered by each entry of a `RankSelect` directory.
+const BLOCK_LEN: usize = 512;
+
+/// A rank/select directory over a sequence of bits.
+///
+/// Stores the number of 1s preceding every `BLOCK_LEN`-bit block, so rank
+/// only ever needs to popcount part of a single block, and select only needs
+/// to binary-search the directory before scanning a single block.
+///
+/// The directory does not own the bits it indexes, so it must be updated
+/// whenever they change, either through `set` or `changed_from`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct RankSelect {
+  /// Number of 1s before the start of each block.
+  blocks: Vec<usize>,
+  /// Total number of 1s in the indexed bits.
+  ones: usize,
+}
+impl RankSelect {
+  pub(crate) fn new<O: BitOrder, T: BitStore>(bits: &BitSlice<O, T>) -> Self {
+    let mut blocks = Vec::with_capacity(bits.len() / BLOCK_LEN + 1);
+    let mut ones = 0;
+    if !bits.is_empty() {
+      for block in bits.chunks(BLOCK_LEN) {
+        blocks.push(ones);
+        ones += block.count_ones();
+      }
+    }
+    RankSelect {
+      blocks,
+      ones,
+    }
+  }
+  /// Number of 1s in all of the indexed bits.
+  pub(crate) fn ones(&self) -> usize {
+    self.ones
+  }
+  /// Sets the bit at `pos` to `value`, updating only the blocks after it.
+  pub(crate) fn set<O: BitOrder, T: BitStore>(&mut self, bits: &mut BitSlice<O, T>, pos: usize, value: bool) {
+    if bits[pos] == value { return }
+    bits.set(pos, value);
+    for ones in self.blocks[pos / BLOCK_LEN + 1..].iter_mut() {
+      if value { *ones += 1 } else { *ones -= 1 }
+    }
+    if value { self.ones += 1 } else { self.ones -= 1 }
+  }
+  /// Recounts every block from the one containing `pos` onwards, after bits
+  /// have been inserted into or removed from `bits` at `pos` or beyond.
+  pub(crate) fn changed_from<O: BitOrder, T: BitStore>(&mut self, bits: &BitSlice<O, T>, pos: usize) {
+    /* The blocks before the change still count the same 1s */
+    let keep = std::cmp::min(pos / BLOCK_LEN, self.blocks.len());
+    let mut ones = match self.blocks.get(keep) {
+      Some(&ones) => ones,
+      None => self.ones,
+    };
+    self.blocks.truncate(keep);
+    let block_start = std::cmp::min(keep * BLOCK_LEN, bits.len());
+    for block in bits[block_start..].chunks(BLOCK_LEN) {
+      self.blocks.push(ones);
+      ones += block.count_ones();
+    }
+    self.ones = ones;
+  }
+  /// Number of 1s in `bits[0..pos]`.
+  pub(crate) fn rank<O: BitOrder, T: BitStore>(&self, bits: &BitSlice<O, T>, pos: usize) -> usize {
+    let block = pos / BLOCK_LEN;
+    if block >= self.blocks.len() { return self.ones }
+    let block_start = block * BLOCK_LEN;
+    if pos == block_start { return self.blocks[block] }
+    self.blocks[block] + bits[block_start..pos].count_ones()
+  }
+  /// Position of the `n`th 1 in `bits`, counting from 0.
+  ///
+  /// Unlike `rank` this is not constant time: it binary-searches the directory,
+  /// taking O(log(bits.len() / BLOCK_LEN)), and then scans a single block.
+  pub(crate) fn select<O: BitOrder, T: BitStore>(&self, bits: &BitSlice<O, T>, n: usize) -> Option<usize> {
+    if n >= self.ones { return None }
+    /* Find the last block that starts with n or fewer 1s before it */
+    let block = match self.blocks.binary_search(&n) {
+      Ok(mut b) => {
+        /* Skip forward past any blocks containing no 1s */
+        while b+1 < self.blocks.len() && self.blocks[b+1] == n { b += 1; }
+        b
+      },
+      Err(b) => b-1,
+    };
+    let block_start = block * BLOCK_LEN;
+    let block_end = std::cmp::min(block_start + BLOCK_LEN, bits.len());
+    let mut remaining = n - self.blocks[block];
+    for (offset, bit) in bits[block_start..block_end].iter().enumerate() {
+      if *bit {
+        if remaining == 0 { return Some(block_start + offset) }
+        remaining -= 1;
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bitvec::prelude::{bitvec, BitVec};
+  fn naive_rank(bits: &BitVec, pos: usize) -> usize {
+    bits[..pos].iter().filter(|bit| **bit).count()
+  }
+  #[test]
+  fn rank_0() {
+    let bits = bitvec![0,1,1,1, 1,1,0,1, 1,0,0,0, 1,0,0,0];
+    let rs = RankSelect::new(&bits);
+    for pos in 0..=bits.len() {
+      assert_eq!(naive_rank(&bits, pos), rs.rank(&bits, pos));
+    }
+    assert_eq!(Some(12), rs.select(&bits, 7));
+    assert_eq!(None, rs.select(&bits, 8));
+  }
+  #[test]
+  fn rank_1() {
+    let bits: BitVec = (0..5000).map(|i| i % 7 == 0 || i % 13 == 0).collect();
+    let rs = RankSelect::new(&bits);
+    for pos in 0..=bits.len() {
+      assert_eq!(naive_rank(&bits, pos), rs.rank(&bits, pos));
+    }
+  }
+  #[test]
+  fn select_0() {
+    let bits: BitVec = (0..5000).map(|i| (1000..2100).contains(&i) && i % 3 == 0).collect();
+    let rs = RankSelect::new(&bits);
+    let ones: Vec<usize> = (0..bits.len()).filter(|&i| bits[i]).collect();
+    for (n, &pos) in ones.iter().enumerate() {
+      assert_eq!(Some(pos), rs.select(&bits, n));
+    }
+    assert_eq!(None, rs.select(&bits, ones.len()));
+  }
+  #[test]
+  fn ones_in_range_0() {
+    let ranges = [
+      bitvec![0,1,1,1,0,0,1,0,1,1,0,0],
+      bitvec![0,0,0,0,0,0,1],
+      bitvec![0,1,1,1,1,1,1,0,1,0,0,1],
+    ];
+    let num_ones = [6, 1, 8];
+    for (bits, &ones) in ranges.iter().zip(num_ones.iter()) {
+      let rs = RankSelect::new(bits);
+      assert_eq!(ones, rs.rank(bits, bits.len()) - rs.rank(bits, 0));
+      assert_eq!(ones, rs.ones());
+    }
+  }
+  #[test]
+  fn set_0() {
+    let mut bits: BitVec = (0..3000).map(|i| i % 5 == 0).collect();
+    let mut rs = RankSelect::new(&bits);
+    for i in 0..bits.len() {
+      let pos = (i * 7919) % bits.len();
+      let value = i % 3 != 0;
+      rs.set(&mut bits, pos, value);
+      assert_eq!(value, bits[pos]);
+    }
+    assert_eq!(RankSelect::new(&bits), rs);
+  }
+  #[test]
+  fn changed_from_0() {
+    let mut bits: BitVec = (0..3000).map(|i| i % 3 == 0 || i % 11 == 0).collect();
+    let mut rs = RankSelect::new(&bits);
+    for &pos in [2500, 1024, 700, 3, 0].iter() {
+      for _ in 0..100 { bits.insert(pos, true); }
+      rs.changed_from(&bits, pos);
+      assert_eq!(RankSelect::new(&bits), rs);
+    }
+    for &pos in [0, 511, 1500].iter() {
+      for _ in 0..600 { bits.remove(pos); }
+      rs.changed_from(&bits, pos);
+      assert_eq!(RankSelect::new(&bits), rs);
+    }
+    bits.truncate(0);
+    rs.changed_from(&bits, 0);
+    assert_eq!(RankSelect::new(&bits), rs);
+  }
+  #[test]
+  fn empty() {
+    let bits: BitVec = BitVec::new();
+    let rs = RankSelect::new(&bits);
+    assert_eq!(0, rs.rank(&bits, 0));
+    assert_eq!(None, rs.select(&bits, 0));
+  }
+}
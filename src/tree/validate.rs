@@ -0,0 +1,307 @@
+use {
+  bitvec::prelude::{BitOrder, BitSlice, BitStore},
+  crate::{
+    error::{K2TreeError as Error, StructureError},
+    tree::{K2Tree, RangeOnes, rank::RankSelect},
+  },
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl K2Tree {
+  /// Checks that the K2Tree is intact, which may not be the case after its `stems`,
+  /// `leaves` or `max_slayers` fields have been modified directly.
+  ///
+  /// Returns a StructureError describing the first invariant found not to hold:
+  /// that every k value is at least 2 and the stem_k and max_slayers match the
+  /// k values of the stem-layers, that the stems and leaves are whole blocks,
+  /// that every stem-layer has one stem for each 1 in the layer above it, that there
+  /// is one leaf for each 1 in the last stem-layer and nothing after it, that no
+  /// block other than the root is all 0s, that the K2Tree has been reindexed
+  /// since its stems or leaves last changed, and that there are no 1s outside of its width
+  /// and height.
+  /// ```
+  /// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+  ///   use k2_tree::{K2Tree, error::{K2TreeError, StructureError}};
+  ///   let mut tree = K2Tree::from_coords(vec![(0, 0), (7, 7)], 2, 2)?;
+  ///   tree.validate()?;
+  ///   tree.leaves.set(0, false);
+  ///   assert_eq!(
+  ///     Err(K2TreeError::from(StructureError::EmptyLeaf { leaf: 0 })),
+  ///     tree.validate()
+  ///   );
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn validate(&self) -> Result<()> {
+    if let Some(stem_ks) = &self.stem_ks {
+      if stem_ks.len() != self.max_slayers || stem_ks[0] != self.stem_k {
+        return Err(StructureError::StemKsMismatch {
+          stem_k: self.stem_k,
+          max_slayers: self.max_slayers,
+          stem_ks: stem_ks.clone(),
+        }.into())
+      }
+    }
+    check_structure(&self.stem_ks(), self.leaf_k, &self.stems, &self.leaves)?;
+    if self.stem_ranks != RankSelect::new(&self.stems)
+    || self.leaf_ranks != RankSelect::new(&self.leaves)
+    || self.slayer_starts != self.build_layer_starts() {
+      return Err(StructureError::StaleIndex.into())
+    }
+    self.check_dimensions()?;
+    Ok(())
+  }
+  /* Checks that the width and height fit in the matrix_width, and that every 1
+  is inside them. Relies on the K2Tree being otherwise intact. */
+  pub(crate) fn check_dimensions(&self) -> std::result::Result<(), StructureError> {
+    let [width, height] = match self.dimensions {
+      Some(dimensions) => dimensions,
+      None => return Ok(()),
+    };
+    let matrix_width = self.matrix_width();
+    if width > matrix_width || height > matrix_width {
+      return Err(StructureError::OversizedDimensions { width, height, matrix_width })
+    }
+    /* The columns right of the width, then the rows below the height */
+    let mut outside = RangeOnes::new(self, width, matrix_width-1, 0, matrix_width-1)
+      .chain(RangeOnes::new(self, 0, matrix_width-1, height, matrix_width-1));
+    if let Some((x, y)) = outside.next() {
+      return Err(StructureError::OneOutsideDimensions { x_y: [x, y] })
+    }
+    Ok(())
+  }
+}
+
+/* Checks that stems and leaves with these k values for each stem-layer and the leaves
+describe a valid K2Tree, returning the first invariant found not to hold.
+Each layer is walked in turn from the root, the number of 1s in a layer giving the
+number of stems (or leaves, after the last layer) in the next. */
+pub(crate) fn check_structure<O: BitOrder, T: BitStore>(
+  stem_ks: &[usize],
+  leaf_k: usize,
+  stems: &BitSlice<O, T>,
+  leaves: &BitSlice<O, T>,
+) -> std::result::Result<(), StructureError> {
+  if let Some(&stem_k) = stem_ks.iter().find(|&&stem_k| stem_k < 2) {
+    return Err(StructureError::SmallStemKValue { stem_k })
+  }
+  if leaf_k < 2 {
+    return Err(StructureError::SmallLeafKValue { leaf_k })
+  }
+  let max_slayers = stem_ks.len();
+  if max_slayers == 0 {
+    return Err(StructureError::NoStemLayers)
+  }
+  /* Every block must fit in a usize before any lengths are worked out from them */
+  for &stem_k in stem_ks.iter() { block_len(stem_k)?; }
+  let leaf_len = block_len(leaf_k)?;
+  /* Only stems that are all the same length must be a whole number of them */
+  let root_len = stem_ks[0] * stem_ks[0];
+  let uniform = stem_ks.iter().all(|&stem_k| stem_k == stem_ks[0]);
+  if stems.len() < root_len || (uniform && stems.len() % root_len != 0) {
+    return Err(StructureError::PartialStem { len: stems.len(), stem_len: root_len })
+  }
+  if leaves.len() % leaf_len != 0 {
+    return Err(StructureError::PartialLeaf { len: leaves.len(), leaf_len })
+  }
+  let mut layer_start = 0;
+  let mut layer_len = root_len;
+  let mut stems_before = 0;
+  for (layer, &stem_k) in stem_ks.iter().enumerate() {
+    let stem_len = stem_k * stem_k;
+    let remaining = stems.len() - layer_start;
+    if remaining < layer_len {
+      return Err(StructureError::StemCount {
+        layer,
+        expected: layer_len / stem_len,
+        found: remaining / stem_len,
+      })
+    }
+    let layer_stems = &stems[layer_start..layer_start+layer_len];
+    /* The root is the only stem allowed to be empty, in an empty K2Tree */
+    if layer > 0 {
+      if let Some(i) = layer_stems.chunks(stem_len).position(|stem| stem.not_any()) {
+        return Err(StructureError::EmptyStem {
+          layer,
+          stem: stems_before + i,
+        })
+      }
+    }
+    let ones = layer_stems.count_ones();
+    stems_before += layer_len / stem_len;
+    layer_start += layer_len;
+    /* So many blocks that their length does not fit in a usize cannot all be there */
+    layer_len = match stem_ks.get(layer+1) {
+      Some(next_k) => match ones.checked_mul(next_k * next_k) {
+        Some(len) => len,
+        None => return Err(StructureError::StemCount {
+          layer: layer+1,
+          expected: ones,
+          found: (stems.len() - layer_start) / (next_k * next_k),
+        }),
+      },
+      None => match ones.checked_mul(leaf_len) {
+        Some(len) => len,
+        None => return Err(StructureError::LeafCount {
+          expected: ones,
+          found: leaves.len() / leaf_len,
+        }),
+      },
+    };
+  }
+  if layer_start != stems.len() {
+    let stem_len = stem_ks[max_slayers-1].pow(2);
+    return Err(StructureError::StemCount {
+      layer: max_slayers,
+      expected: 0,
+      found: (stems.len() - layer_start).div_ceil(stem_len),
+    })
+  }
+  if leaves.len() != layer_len {
+    return Err(StructureError::LeafCount {
+      expected: layer_len / leaf_len,
+      found: leaves.len() / leaf_len,
+    })
+  }
+  if let Some(leaf) = leaves.chunks(leaf_len).position(|leaf| leaf.not_any()) {
+    return Err(StructureError::EmptyLeaf { leaf })
+  }
+  Ok(())
+}
+/* The number of bits in a block with this k value, or an OversizedBlock error
+if it does not fit in a usize */
+pub(crate) fn block_len(k: usize) -> std::result::Result<usize, StructureError> {
+  k.checked_mul(k).ok_or(StructureError::OversizedBlock { k })
+}
+/* The matrix_width of a K2Tree with these k values, or None if it does not fit in a usize */
+pub(crate) fn checked_matrix_width(stem_ks: impl IntoIterator<Item=usize>, leaf_k: usize) -> Option<usize> {
+  stem_ks.into_iter().try_fold(leaf_k, |width, stem_k| width.checked_mul(stem_k))
+}
+/* The length of the stems of a valid K2Tree with these k values for each stem-layer,
+or None if the stems end before the last stem-layer */
+pub(crate) fn stems_len<O: BitOrder, T: BitStore>(
+  stem_ks: &[usize],
+  stems: &BitSlice<O, T>,
+) -> Option<usize> {
+  let mut layer_start = 0;
+  let mut layer_len = block_len(*stem_ks.first()?).ok()?;
+  for &next_k in stem_ks.iter().skip(1) {
+    if layer_len > stems.len() - layer_start { return None }
+    let ones = stems[layer_start..layer_start+layer_len].count_ones();
+    layer_start += layer_len;
+    layer_len = ones.checked_mul(block_len(next_k).ok()?)?;
+  }
+  if layer_len > stems.len() - layer_start { return None }
+  Some(layer_start + layer_len)
+}
+
+#[cfg(test)]
+mod api {
+  use {
+    super::*,
+    bitvec::prelude::{bitvec, BitVec},
+  };
+  fn check(stem_k: usize, leaf_k: usize, max_slayers: usize, stems: BitVec, leaves: BitVec) -> std::result::Result<(), StructureError> {
+    check_structure(&vec![stem_k; max_slayers], leaf_k, &stems, &leaves)
+  }
+  #[test]
+  fn valid_trees() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let coords: Vec<(usize, usize)> = (0..150)
+          .map(|_| (rng.gen_range(0, 100), rng.gen_range(0, 100)))
+          .collect();
+        let mut tree = K2Tree::from_coords(coords.clone(), stem_k, leaf_k)?;
+        tree.validate()?;
+        K2Tree::with_k(stem_k, leaf_k)?.validate()?;
+        for &(x, y) in coords.iter().take(100) {
+          tree.set(x, y, false)?;
+          tree.validate()?;
+        }
+        for &(x, y) in coords.iter().take(50) {
+          tree.set(x, y, true)?;
+          tree.validate()?;
+        }
+      }
+    }
+    Ok(())
+  }
+  #[test]
+  fn invalid_trees() {
+    use StructureError::*;
+    assert_eq!(Err(SmallStemKValue { stem_k: 1 }), check(1, 2, 1, bitvec![1], bitvec![]));
+    assert_eq!(Err(SmallLeafKValue { leaf_k: 0 }), check(2, 0, 1, bitvec![0; 4], bitvec![]));
+    assert_eq!(Err(OversizedBlock { k: usize::MAX }), check(usize::MAX, 2, 1, bitvec![0], bitvec![]));
+    assert_eq!(Err(OversizedBlock { k: usize::MAX }), check(2, usize::MAX, 1, bitvec![0; 4], bitvec![]));
+    assert_eq!(Err(NoStemLayers), check(2, 2, 0, bitvec![0; 4], bitvec![]));
+    assert_eq!(
+      Err(PartialStem { len: 0, stem_len: 4 }),
+      check(2, 2, 1, bitvec![], bitvec![])
+    );
+    assert_eq!(
+      Err(PartialLeaf { len: 3, leaf_len: 4 }),
+      check(2, 2, 1, bitvec![1,0,0,0], bitvec![1,0,0])
+    );
+    /* The root points to two stems, but only one follows it */
+    assert_eq!(
+      Err(StemCount { layer: 1, expected: 2, found: 1 }),
+      check(2, 2, 2, bitvec![1,0,0,1, 1,0,0,0], bitvec![1,0,0,0])
+    );
+    /* A stem after the last stem-layer */
+    assert_eq!(
+      Err(StemCount { layer: 1, expected: 0, found: 1 }),
+      check(2, 2, 1, bitvec![1,0,0,0, 1,0,0,0], bitvec![1,0,0,0])
+    );
+    assert_eq!(
+      Err(LeafCount { expected: 2, found: 1 }),
+      check(2, 2, 2, bitvec![1,0,0,0, 1,1,0,0], bitvec![1,0,0,0])
+    );
+    assert_eq!(
+      Err(EmptyStem { layer: 1, stem: 2 }),
+      check(2, 2, 2, bitvec![1,1,0,0, 0,0,1,0, 0,0,0,0], bitvec![0,1,0,0])
+    );
+    assert_eq!(
+      Err(EmptyLeaf { leaf: 1 }),
+      check(2, 2, 2, bitvec![1,0,0,0, 0,1,1,0], bitvec![0,1,0,0, 0,0,0,0])
+    );
+    /* An empty root is valid as long as nothing follows it */
+    assert_eq!(Ok(()), check(2, 2, 3, bitvec![0; 4], bitvec![]));
+    assert_eq!(
+      Err(LeafCount { expected: 0, found: 1 }),
+      check(2, 2, 1, bitvec![0; 4], bitvec![1,0,0,0])
+    );
+  }
+  #[test]
+  fn validate() -> Result<()> {
+    let tree = K2Tree::from_coords(vec![(0, 0), (3, 3), (7, 7)], 2, 2)?;
+    /* 1 set in a stem without adding a leaf beneath it */
+    let mut bad = tree.clone();
+    bad.stems.set(5, true);
+    bad.reindex();
+    assert_eq!(
+      Err(Error::from(StructureError::LeafCount { expected: 4, found: 3 })),
+      bad.validate()
+    );
+    /* More stem-layers than the stems have */
+    let mut bad = tree.clone();
+    bad.max_slayers = 3;
+    bad.reindex();
+    assert_eq!(
+      Err(Error::from(StructureError::StemCount { layer: 2, expected: 3, found: 0 })),
+      bad.validate()
+    );
+    /* Stems of another K2Tree swapped in without reindexing */
+    let mut bad = tree.clone();
+    let other = K2Tree::from_coords(vec![(0, 0), (7, 0), (0, 7)], 2, 2)?;
+    bad.stems = other.stems.clone();
+    bad.leaves = other.leaves.clone();
+    assert_eq!(Err(Error::from(StructureError::StaleIndex)), bad.validate());
+    bad.reindex();
+    bad.validate()?;
+    assert_eq!(other, bad);
+    Ok(())
+  }
+}
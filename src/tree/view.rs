@@ -0,0 +1,325 @@
+use {
+  bitvec::prelude::{BitSlice, Lsb0},
+  crate::{
+    error::{K2TreeError as Error, StructureError},
+    tree::{
+      K2Tree,
+      Layout,
+      LeafBit,
+      Predecessors,
+      Range2D,
+      RangeOnes,
+      Successors,
+      binary,
+      rank::RankSelect,
+      validate::check_structure,
+    },
+  },
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A read-only K2Tree that borrows its stems and leaves straight from bytes in the
+/// binary format written by `K2Tree::write_to`, such as a memory-mapped file.
+///
+/// The stems and leaves are never copied. The only things built when a view is created
+/// are a rank directory over the stems, which takes up one word for every 512 bits of stems,
+/// and the start of each stem-layer.
+/// ```
+/// fn main() -> Result<(), k2_tree::error::K2TreeError> {
+///   use k2_tree::{K2Tree, tree::K2TreeView};
+///   let tree = K2Tree::from_coords(vec![(0, 5), (12, 3), (7, 3)], 2, 2)?;
+///   let mut bytes = Vec::new();
+///   tree.write_to(&mut bytes)?;
+///   let view = K2TreeView::new(&bytes)?;
+///   assert_eq!(true, view.get(12, 3)?);
+///   assert_eq!(vec![7, 12], view.successors(3)?);
+///   assert_eq!(tree, view.to_tree());
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct K2TreeView<'a> {
+  stem_k: usize,
+  leaf_k: usize,
+  max_slayers: usize,
+  width: usize,
+  height: usize,
+  stems: &'a BitSlice<Lsb0, u8>,
+  leaves: &'a BitSlice<Lsb0, u8>,
+  stem_ranks: RankSelect,
+  stem_ks: Vec<usize>,
+  slayer_starts: Vec<usize>,
+}
+impl<'a> K2TreeView<'a> {
+  /// Creates a view of the K2Tree in `bytes`, which must be in the binary format
+  /// written by `K2Tree::write_to`. Any bytes after the end of the leaves are ignored.
+  ///
+  /// Returns an error under the same conditions as `K2Tree::read_from`, or an
+  /// InvalidFormat error if `bytes` ends before the end of the leaves.
+  pub fn new(bytes: &'a [u8]) -> Result<Self> {
+    let header = binary::parse_header(bytes)?;
+    let stems_end = header.len + binary::packed_len(header.stems_len);
+    let leaves_end = stems_end + binary::packed_len(header.leaves_len);
+    if bytes.len() < leaves_end {
+      return Err(Error::InvalidFormat {
+        reason: "Input ends before the end of the leaves".into()
+      })
+    }
+    let stems = &BitSlice::<Lsb0, u8>::from_slice(&bytes[header.len..stems_end])[..header.stems_len];
+    let leaves = &BitSlice::<Lsb0, u8>::from_slice(&bytes[stems_end..leaves_end])[..header.leaves_len];
+    check_structure(&header.stem_ks, header.leaf_k, stems, leaves)?;
+    let matrix_width = header.leaf_k * header.stem_ks.iter().product::<usize>();
+    let [width, height] = header.dimensions;
+    if width > matrix_width || height > matrix_width {
+      return Err(StructureError::OversizedDimensions { width, height, matrix_width }.into())
+    }
+    /* Each layer has a stem for each 1 in the layer above it */
+    let mut slayer_starts = vec![0];
+    let mut layer_len = header.stem_k * header.stem_k;
+    for next_k in header.stem_ks.iter().skip(1) {
+      let layer_start = slayer_starts[slayer_starts.len()-1];
+      let ones = stems[layer_start..layer_start+layer_len].count_ones();
+      slayer_starts.push(layer_start + layer_len);
+      layer_len = ones * next_k * next_k;
+    }
+    let view = K2TreeView {
+      stem_k: header.stem_k,
+      leaf_k: header.leaf_k,
+      max_slayers: header.max_slayers,
+      width,
+      height,
+      stems,
+      leaves,
+      stem_ranks: RankSelect::new(stems),
+      stem_ks: header.stem_ks,
+      slayer_starts,
+    };
+    /* The columns right of the width, then the rows below the height */
+    let range_max = matrix_width-1;
+    let outside = [
+      Range2D::new(width, range_max, 0, range_max),
+      Range2D::new(0, range_max, height, range_max),
+    ];
+    for &query in outside.iter().filter(|query| query.min_x <= query.max_x && query.min_y <= query.max_y) {
+      if let Some((x, y)) = RangeOnes::from_layout(view.layout(), query).next() {
+        return Err(StructureError::OneOutsideDimensions { x_y: [x, y] }.into())
+      }
+    }
+    Ok(view)
+  }
+  /// The k value of the K2Tree's stems, or of its root if each stem-layer has its own k value.
+  pub fn stem_k(&self) -> usize {
+    self.stem_k
+  }
+  /// Returns the k value of each of the K2Tree's stem-layers, the same as `K2Tree::stem_ks`.
+  pub fn stem_ks(&self) -> Vec<usize> {
+    self.stem_ks.clone()
+  }
+  /// The k value of the K2Tree's leaves.
+  pub fn leaf_k(&self) -> usize {
+    self.leaf_k
+  }
+  /// The maximum number of stem-layers possible given the matrix_width.
+  pub fn max_slayers(&self) -> usize {
+    self.max_slayers
+  }
+  /// Returns true if the matrix the K2Tree represents contains only 0s.
+  pub fn is_empty(&self) -> bool {
+    self.leaves.is_empty()
+  }
+  /// Returns the width of the square bit-matrix that the K2Tree's stems and leaves cover.
+  pub fn matrix_width(&self) -> usize {
+    self.leaf_k * self.stem_ks.iter().product::<usize>()
+  }
+  /// Returns the width of the bit-matrix that the K2Tree represents, the same as `K2Tree::width`.
+  pub fn width(&self) -> usize {
+    self.width
+  }
+  /// Returns the height of the bit-matrix that the K2Tree represents, the same as `K2Tree::height`.
+  pub fn height(&self) -> usize {
+    self.height
+  }
+  /// Returns the state of the bit at the coordinates (x, y), the same as `K2Tree::get`.
+  pub fn get(&self, x: usize, y: usize) -> Result<bool> {
+    self.check_bounds(x, y)?;
+    Ok(self.layout().get(x, y))
+  }
+  /// Returns the x-coordinates of the 1s in row `y`, the same as `K2Tree::successors`.
+  pub fn successors(&self, y: usize) -> Result<Vec<usize>> {
+    Ok(self.iter_successors(y)?.collect())
+  }
+  /// Returns an iterator over the x-coordinates of the 1s in row `y`, the same as
+  /// `K2Tree::iter_successors`.
+  pub fn iter_successors(&self, y: usize) -> Result<Successors<'_, Lsb0, u8>> {
+    self.check_bounds(0, y)?;
+    Ok(Successors::from_layout(self.layout(), y))
+  }
+  /// Returns the y-coordinates of the 1s in column `x`, the same as `K2Tree::predecessors`.
+  pub fn predecessors(&self, x: usize) -> Result<Vec<usize>> {
+    Ok(self.iter_predecessors(x)?.collect())
+  }
+  /// Returns an iterator over the y-coordinates of the 1s in column `x`, the same as
+  /// `K2Tree::iter_predecessors`.
+  pub fn iter_predecessors(&self, x: usize) -> Result<Predecessors<'_, Lsb0, u8>> {
+    self.check_bounds(x, 0)?;
+    Ok(Predecessors::from_layout(self.layout(), x))
+  }
+  /// Returns an iterator over the K2Tree's leaves which produces instances of LeafBit,
+  /// the same as `K2Tree::leaves`.
+  pub fn leaves(&self) -> ViewLeaves<'_> {
+    ViewLeaves {
+      view: self,
+      pos: 0,
+    }
+  }
+  /// Copies the stems and leaves into an owned K2Tree.
+  pub fn to_tree(&self) -> K2Tree {
+    let mut tree = K2Tree::from_bits_with_stem_ks(
+      self.stem_ks.clone(),
+      self.leaf_k,
+      self.stems.iter().copied().collect(),
+      self.leaves.iter().copied().collect(),
+    );
+    tree.set_dimensions(self.width, self.height);
+    tree
+  }
+}
+
+/* Private */
+impl<'a> K2TreeView<'a> {
+  fn layout(&self) -> Layout<'_, Lsb0, u8> {
+    Layout {
+      stem_k: self.stem_k,
+      stem_ks: Some(&self.stem_ks),
+      leaf_k: self.leaf_k,
+      max_slayers: self.max_slayers,
+      stems: self.stems,
+      leaves: self.leaves,
+      stem_ranks: &self.stem_ranks,
+      slayer_starts: &self.slayer_starts,
+    }
+  }
+  fn leaf_len(&self) -> usize {
+    self.leaf_k * self.leaf_k
+  }
+  fn check_bounds(&self, x: usize, y: usize) -> Result<()> {
+    if x >= self.width || y >= self.height {
+      return Err(Error::Read {
+        source: Box::new(Error::OutOfBounds {
+          x_y: [x, y],
+          min_x_y: [0, 0],
+          max_x_y: [self.width.saturating_sub(1), self.height.saturating_sub(1)]
+        })
+      })
+    }
+    Ok(())
+  }
+}
+
+/// An iterator over a K2TreeView's leaves which produces instances of LeafBit.
+#[derive(Debug)]
+pub struct ViewLeaves<'a> {
+  view: &'a K2TreeView<'a>,
+  pos: usize,
+}
+impl<'a> Iterator for ViewLeaves<'a> {
+  type Item = LeafBit;
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.pos == self.view.leaves.len() { return None }
+    let [x, y] = self.view.layout().get_coords(self.pos);
+    let leaf_len = self.view.leaf_len();
+    let leaf_bit = LeafBit {
+      value: self.view.leaves[self.pos],
+      x,
+      y,
+      leaf: self.pos / leaf_len,
+      bit: self.pos % leaf_len,
+    };
+    self.pos += 1;
+    Some(leaf_bit)
+  }
+}
+
+#[cfg(test)]
+mod api {
+  use super::*;
+  fn to_bytes(tree: &K2Tree) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes)?;
+    Ok(bytes)
+  }
+  #[test]
+  fn matches_tree() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for stem_k in 2..5 {
+      for leaf_k in 2..5 {
+        let coords: Vec<(usize, usize)> = (0..150)
+          .map(|_| (rng.gen_range(0, 70), rng.gen_range(0, 70)))
+          .collect();
+        for tree in [K2Tree::from_coords(coords, stem_k, leaf_k)?, K2Tree::with_k(stem_k, leaf_k)?].iter() {
+          let bytes = to_bytes(tree)?;
+          let view = K2TreeView::new(&bytes)?;
+          assert_eq!(tree.matrix_width(), view.matrix_width());
+          assert_eq!(tree.is_empty(), view.is_empty());
+          for i in 0..tree.matrix_width() {
+            assert_eq!(tree.successors(i)?, view.successors(i)?);
+            assert_eq!(tree.predecessors(i)?, view.predecessors(i)?);
+          }
+          for y in 0..tree.matrix_width() {
+            for x in 0..tree.matrix_width() {
+              assert_eq!(tree.get(x, y)?, view.get(x, y)?);
+            }
+          }
+          assert!(tree.leaves().eq(view.leaves()));
+          assert_eq!(*tree, view.to_tree());
+          let width = tree.matrix_width();
+          assert!(view.get(width, 0).is_err());
+          assert!(view.successors(width).is_err());
+          assert!(view.predecessors(width).is_err());
+          assert!(view.iter_successors(width).is_err());
+        }
+      }
+    }
+    Ok(())
+  }
+  #[test]
+  fn stem_ks() -> Result<()> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut tree = K2Tree::with_stem_ks(&[4, 3, 2], 3)?;
+    for _ in 0..150 {
+      tree.set(rng.gen_range(0, 72), rng.gen_range(0, 72), true)?;
+    }
+    let bytes = to_bytes(&tree)?;
+    let view = K2TreeView::new(&bytes)?;
+    assert_eq!(vec![4, 3, 2], view.stem_ks());
+    assert_eq!(72, view.matrix_width());
+    for i in 0..72 {
+      assert_eq!(tree.successors(i)?, view.successors(i)?);
+      assert_eq!(tree.predecessors(i)?, view.predecessors(i)?);
+      assert!(tree.iter_successors(i)?.eq(view.iter_successors(i)?));
+      assert!(tree.iter_predecessors(i)?.eq(view.iter_predecessors(i)?));
+    }
+    for y in 0..72 {
+      for x in 0..72 {
+        assert_eq!(tree.get(x, y)?, view.get(x, y)?);
+      }
+    }
+    assert!(tree.leaves().eq(view.leaves()));
+    assert_eq!(tree, view.to_tree());
+    Ok(())
+  }
+  #[test]
+  fn borrows_bytes() -> Result<()> {
+    let tree = K2Tree::from_coords(vec![(0, 0), (7, 7)], 2, 2)?;
+    let mut bytes = to_bytes(&tree)?;
+    /* Trailing bytes are ignored, missing ones are an error */
+    bytes.extend_from_slice(&[0xff; 5]);
+    assert_eq!(tree, K2TreeView::new(&bytes)?.to_tree());
+    bytes.truncate(bytes.len() - 6);
+    assert!(matches!(K2TreeView::new(&bytes), Err(Error::InvalidFormat{..})));
+    Ok(())
+  }
+}